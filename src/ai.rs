@@ -0,0 +1,400 @@
+use game::*;
+use input::*;
+use player::*;
+use tile::*;
+use projectile::*;
+
+use std::cmp::Ordering;
+
+//how far ahead of the robot's centre a bot checks for holes before walking on
+const HOLE_LOOKAHEAD : f32 = 20.0;
+//projectiles passing closer than this to the robot's centre count as a hit
+const DODGE_RADIUS : f32 = 30.0;
+//half a tile, projectiles are positioned by their top left corner
+const PROJECTILE_HALF : f32 = 16.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Difficulty
+{
+    Easy,
+    Normal,
+    Hard,
+}
+
+struct Settings
+{
+    //ticks between decisions, the bot keeps doing what it last chose in between so this
+    //has to stay under the aim tolerance divided by the player speed or it walks past its mark
+    think_ticks: u32,
+    dodge_ticks: f32,
+    //0 throws at where the target is, 1 throws at where it will be
+    lead: f32,
+    //how far off the target's axis still counts as lined up for a throw
+    aim_tolerance: f32,
+}
+
+impl Difficulty
+{
+    pub fn from_name( name: &str ) -> Option<Difficulty>
+    {
+        match name
+        {
+            "easy" => Some( Difficulty::Easy ),
+            "normal" => Some( Difficulty::Normal ),
+            "hard" => Some( Difficulty::Hard ),
+            _ => None,
+        }
+    }
+
+    fn get_settings( &self ) -> Settings
+    {
+        match *self
+        {
+            Difficulty::Easy => Settings { think_ticks: 12, dodge_ticks: 0.0, lead: 0.0, aim_tolerance: 24.0 },
+            Difficulty::Normal => Settings { think_ticks: 8, dodge_ticks: 25.0, lead: 0.5, aim_tolerance: 12.0 },
+            Difficulty::Hard => Settings { think_ticks: 3, dodge_ticks: 45.0, lead: 1.0, aim_tolerance: 6.0 },
+        }
+    }
+}
+
+fn is_held( input: &PlayerInput, dir: Direction ) -> bool
+{
+    match dir
+    {
+        Direction::UP => input.up,
+        Direction::DOWN => input.down,
+        Direction::LEFT => input.left,
+        Direction::RIGHT => input.right,
+    }
+}
+
+fn hold( input: &mut PlayerInput, dir: Direction )
+{
+    match dir
+    {
+        Direction::UP => { input.up = true }
+        Direction::DOWN => { input.down = true }
+        Direction::LEFT => { input.left = true }
+        Direction::RIGHT => { input.right = true }
+    }
+}
+
+//a computer player, it only ever sees the game and answers with the same input a keyboard would give
+pub struct Bot
+{
+    index: usize,
+    difficulty: Difficulty,
+    wait: u32,
+    plan: PlayerInput,
+    last: PlayerInput,
+    turning: bool,
+    round: u32,
+}
+
+impl Bot
+{
+    pub fn new( index: usize, difficulty: Difficulty ) -> Bot
+    {
+        Bot
+        {
+            index,
+            difficulty,
+            wait: 0,
+            plan: PlayerInput::default(),
+            last: PlayerInput::default(),
+            turning: false,
+            round: 0,
+        }
+    }
+
+    pub fn get_index( &self ) -> usize
+    {
+        self.index
+    }
+
+    //called once per tick, before the game is ticked with the returned input
+    pub fn think( &mut self, game: &Game ) -> PlayerInput
+    {
+        let player = match game.players.get( self.index )
+        {
+            Some( player ) => player,
+            None => return PlayerInput::default(),
+        };
+        if player.is_dead()
+        {
+            return PlayerInput::default();
+        }
+
+        let settings = self.difficulty.get_settings();
+        //bots are deterministic, so stagger their first decision differently every round or
+        //evenly matched bots can draw the same round forever
+        if game.get_round() != self.round
+        {
+            self.round = game.get_round();
+            self.wait = ( self.index as u32 + self.round ) % settings.think_ticks;
+            self.plan = PlayerInput::default();
+        }
+        let mut lookahead = HOLE_LOOKAHEAD;
+        if self.wait == 0
+        {
+            self.wait = settings.think_ticks;
+            self.plan = self.decide( game, player, &settings );
+            //a turn takes a tick or two, decide again right after instead of standing around
+            //the press only lasts a tick and robots pick up speed gradually, so turning to face
+            //the edge of a hole is safe
+            if self.turning
+            {
+                self.wait = 0;
+                self.turning = false;
+                lookahead = PLAYER_SPEED / 2.0;
+            }
+        }
+        else
+        {
+            self.wait -= 1;
+        }
+
+        let mut input = self.plan;
+        //an action is a single press, don't repeat it while waiting for the next decision
+        self.plan.action = false;
+        //holes are checked every tick, even a slow bot shouldn't walk off the edge between decisions
+        avoid_holes( &mut input, player, &game.tile_map, lookahead );
+        self.last = input;
+        input
+    }
+
+    fn decide( &mut self, game: &Game, player: &Player, settings: &Settings ) -> PlayerInput
+    {
+        if settings.dodge_ticks > 0.0
+        {
+            if let Some( dir ) = get_dodge( game, player, settings.dodge_ticks )
+            {
+                let mut input = PlayerInput::default();
+                hold( &mut input, dir );
+                return input;
+            }
+        }
+
+        //get off crumbling floor before doing anything else
+        if !game.tile_map.is_safe_at( player.get_pos_x(), player.get_pos_y() )
+        {
+            let mut input = PlayerInput::default();
+            walk_towards_center( &mut input, player, &game.tile_map );
+            return input;
+        }
+
+        if player.get_held_tile().is_some()
+        {
+            self.aim( game, player, settings )
+        }
+        else
+        {
+            self.find_tile( player, &game.tile_map )
+        }
+    }
+
+    //turning only happens on a fresh press, so a direction already held has to be let go first
+    fn face( &mut self, player: &Player, dir: Direction, action: bool ) -> PlayerInput
+    {
+        let mut input = PlayerInput::default();
+        if player.is_facing( dir )
+        {
+            input.action = action;
+            return input;
+        }
+        self.turning = true;
+        if !is_held( &self.last, dir )
+        {
+            hold( &mut input, dir );
+        }
+        input
+    }
+
+    fn find_tile( &mut self, player: &Player, tile_map: &TileMap ) -> PlayerInput
+    {
+        let dirs = [player.get_dir(), Direction::UP, Direction::DOWN, Direction::LEFT, Direction::RIGHT];
+        for dir in dirs.iter()
+        {
+            let ( dx, dy ) = dir.get_vec();
+            let next_x = player.get_pos_x() + dx * TILE_SPACE as f32;
+            let next_y = player.get_pos_y() + dy * TILE_SPACE as f32;
+            if tile_map.can_pick_up_at( next_x, next_y )
+            {
+                return self.face( player, *dir, true );
+            }
+        }
+
+        //nothing left around us, head for the middle of the arena where tiles last longest
+        let mut input = PlayerInput::default();
+        walk_towards_center( &mut input, player, tile_map );
+        input
+    }
+
+    fn aim( &mut self, game: &Game, player: &Player, settings: &Settings ) -> PlayerInput
+    {
+        let target = game.players.iter()
+            .filter(|other| !other.is_dead() && game.get_config().can_hit( player.get_id(), other.get_id() ))
+            .min_by(|a, b| {
+                let dist_a = get_dist( player, a.get_pos_x(), a.get_pos_y() );
+                let dist_b = get_dist( player, b.get_pos_x(), b.get_pos_y() );
+                dist_a.partial_cmp( &dist_b ).unwrap_or( Ordering::Equal )
+            });
+        let target = match target
+        {
+            Some( target ) => target,
+            None => return PlayerInput::default(),
+        };
+
+        //where the target will be by the time a tile thrown now reaches it
+        let flight_ticks = get_dist( player, target.get_pos_x(), target.get_pos_y() ) / THROW_SPEED;
+        let aim_x = target.get_pos_x() + target.get_vel_x() * flight_ticks * settings.lead;
+        let aim_y = target.get_pos_y() + target.get_vel_y() * flight_ticks * settings.lead;
+        let dx = aim_x - player.get_pos_x();
+        let dy = aim_y - player.get_pos_y();
+
+        //from further away the tile would only come down short, close in first
+        if ( dx * dx + dy * dy ).sqrt() > PROJECTILE_RANGE
+        {
+            let mut input = PlayerInput::default();
+            walk_towards( &mut input, player, aim_x, aim_y );
+            avoid_holes( &mut input, player, &game.tile_map, HOLE_LOOKAHEAD );
+            if input != PlayerInput::default()
+            {
+                return input;
+            }
+        }
+
+        if dx.abs() <= settings.aim_tolerance
+        {
+            let dir = if dy < 0.0 { Direction::UP } else { Direction::DOWN };
+            return self.face( player, dir, true );
+        }
+        if dy.abs() <= settings.aim_tolerance
+        {
+            let dir = if dx < 0.0 { Direction::LEFT } else { Direction::RIGHT };
+            return self.face( player, dir, true );
+        }
+
+        //line up on whichever axis is closer, or the other one if holes are in the way
+        let mut along_x = PlayerInput::default();
+        walk_towards( &mut along_x, player, aim_x, player.get_pos_y() );
+        let mut along_y = PlayerInput::default();
+        walk_towards( &mut along_y, player, player.get_pos_x(), aim_y );
+        let tries = if dx.abs() < dy.abs() { [along_x, along_y] } else { [along_y, along_x] };
+        for input in tries.iter()
+        {
+            let mut input = *input;
+            avoid_holes( &mut input, player, &game.tile_map, HOLE_LOOKAHEAD );
+            if input != PlayerInput::default()
+            {
+                return input;
+            }
+        }
+
+        //cut off from the target, throw its way anyway rather than standing around holding the tile
+        let dir = if dx.abs() > dy.abs()
+        {
+            if dx < 0.0 { Direction::LEFT } else { Direction::RIGHT }
+        }
+        else
+        {
+            if dy < 0.0 { Direction::UP } else { Direction::DOWN }
+        };
+        self.face( player, dir, true )
+    }
+}
+
+fn get_dist( player: &Player, pos_x: f32, pos_y: f32 ) -> f32
+{
+    let dx = pos_x - player.get_pos_x();
+    let dy = pos_y - player.get_pos_y();
+    ( dx * dx + dy * dy ).sqrt()
+}
+
+fn walk_towards( input: &mut PlayerInput, player: &Player, pos_x: f32, pos_y: f32 )
+{
+    let dx = pos_x - player.get_pos_x();
+    let dy = pos_y - player.get_pos_y();
+    if dx > PLAYER_SPEED { input.right = true; }
+    else if dx < -PLAYER_SPEED { input.left = true; }
+    if dy > PLAYER_SPEED { input.down = true; }
+    else if dy < -PLAYER_SPEED { input.up = true; }
+}
+
+fn walk_towards_center( input: &mut PlayerInput, player: &Player, tile_map: &TileMap )
+{
+    let center_x = ( tile_map.get_num_tiles_x() * TILE_SPACE ) as f32 / 2.0;
+    let center_y = ( tile_map.get_num_tiles_y() * TILE_SPACE ) as f32 / 2.0;
+    walk_towards( input, player, center_x, center_y );
+}
+
+//lets go of any direction that would take the robot onto an empty or crumbling tile
+fn avoid_holes( input: &mut PlayerInput, player: &Player, tile_map: &TileMap, lookahead: f32 )
+{
+    let pos_x = player.get_pos_x();
+    let pos_y = player.get_pos_y();
+    //already on crumbling floor, crossing more of it is fine as long as it gets us off
+    let fleeing = !tile_map.is_safe_at( pos_x, pos_y );
+    let can_walk = |x: f32, y: f32| if fleeing { tile_map.is_floor_at( x, y ) } else { tile_map.is_safe_at( x, y ) };
+    if input.up && !can_walk( pos_x, pos_y - lookahead ) { input.up = false; }
+    if input.down && !can_walk( pos_x, pos_y + lookahead ) { input.down = false; }
+    if input.left && !can_walk( pos_x - lookahead, pos_y ) { input.left = false; }
+    if input.right && !can_walk( pos_x + lookahead, pos_y ) { input.right = false; }
+}
+
+//the way to step out of the path of the most urgent projectile headed at us, if any
+fn get_dodge( game: &Game, player: &Player, dodge_ticks: f32 ) -> Option<Direction>
+{
+    let pos_x = player.get_pos_x();
+    let pos_y = player.get_pos_y();
+    let mut threat : Option<( f32, f32, f32 )> = None;
+    for projectile in &game.projectiles
+    {
+        if !game.get_config().can_hit( projectile.get_owner(), player.get_id() )
+        {
+            continue;
+        }
+        let vel_x = projectile.get_vel_x();
+        let vel_y = projectile.get_vel_y();
+        let speed_sq = vel_x * vel_x + vel_y * vel_y;
+        if speed_sq == 0.0
+        {
+            continue;
+        }
+        let rel_x = pos_x - ( projectile.get_pos_x() + PROJECTILE_HALF );
+        let rel_y = pos_y - ( projectile.get_pos_y() + PROJECTILE_HALF );
+        //ticks until the projectile is as close as it will get
+        let ticks = ( rel_x * vel_x + rel_y * vel_y ) / speed_sq;
+        if ticks < 0.0 || ticks > dodge_ticks
+        {
+            continue;
+        }
+        let miss_x = rel_x - vel_x * ticks;
+        let miss_y = rel_y - vel_y * ticks;
+        if miss_x * miss_x + miss_y * miss_y > DODGE_RADIUS * DODGE_RADIUS
+        {
+            continue;
+        }
+        if threat.map_or( true, |( soonest, _, _ )| ticks < soonest )
+        {
+            threat = Some( ( ticks, vel_x, vel_y ) );
+        }
+    }
+
+    let ( _, vel_x, vel_y ) = threat?;
+    let sides = if vel_x.abs() > vel_y.abs()
+    {
+        [Direction::UP, Direction::DOWN]
+    }
+    else
+    {
+        [Direction::LEFT, Direction::RIGHT]
+    };
+    //prefer the side with floor to stand on, a dodge into a hole is no dodge at all
+    sides.iter()
+        .cloned()
+        .find(|dir| {
+            let ( dx, dy ) = dir.get_vec();
+            game.tile_map.is_safe_at( pos_x + dx * TILE_SPACE as f32, pos_y + dy * TILE_SPACE as f32 )
+        })
+}
@@ -1,7 +1,4 @@
-use ggez::graphics;
-use ggez::Context;
-use ggez::GameResult;
-
+#[derive(Clone)]
 pub struct Anim
 {
     pos_x: f32,
@@ -9,16 +6,15 @@ pub struct Anim
     frame: u32,
     end: u32,
     wait: u32,
-    //sprite: graphics::Image,
     is_dead: bool,
     prefix: String,
+    owner: Option<u32>,
 }
 
 impl Anim
 {
-    pub fn new( ctx: &mut Context, pos_x: f32, pos_y: f32, prefix: String, end: u32, wait: u32 ) -> Anim
-    { 
-        //let path = prefix + "1.png";
+    pub fn new( pos_x: f32, pos_y: f32, prefix: String, end: u32, wait: u32 ) -> Anim
+    {
         Anim
         {
             pos_x,
@@ -26,12 +22,20 @@ impl Anim
             frame: 1,
             end,
             wait,
-            //sprite : graphics::Image::new(ctx, path ).unwrap(),
             is_dead : false,
             prefix,
+            owner: None,
         }
     }
 
+    //an animation drawn in the colours of the given player
+    pub fn new_owned( pos_x: f32, pos_y: f32, prefix: String, end: u32, wait: u32, owner: u32 ) -> Anim
+    {
+        let mut anim = Anim::new( pos_x, pos_y, prefix, end, wait );
+        anim.owner = Some( owner );
+        anim
+    }
+
     pub fn update( &mut self )
     {
         if self.frame >= self.end * self.wait
@@ -42,21 +46,34 @@ impl Anim
         self.frame += 1;
     }
 
-    pub fn draw( &mut self, ctx: &mut Context )
+    pub fn get_pos_x( &self ) -> f32
+    {
+        self.pos_x
+    }
+
+    pub fn get_pos_y( &self ) -> f32
+    {
+        self.pos_y
+    }
+
+    pub fn get_owner( &self ) -> Option<u32>
+    {
+        self.owner
+    }
+
+    //variant is spliced between the prefix and the frame number, e.g. a colour for owned animations
+    pub fn get_frame_path( &self, variant: &str ) -> String
     {
-        let dest_point = graphics::Point2::new( self.pos_x as f32, self.pos_y as f32 );
         let mut f : u32 = self.frame / self.wait + 1;
         if f > self.end
         {
             f = self.end;
         }
-        let path = format!("{}{}.png", self.prefix, f );
-        let sprite = graphics::Image::new(ctx, path ).unwrap();
-        graphics::draw(ctx, &sprite, dest_point, 0.0 );
+        format!("{}{}{}.png", self.prefix, variant, f )
     }
 
     pub fn is_dead( &self ) -> bool
     {
         self.is_dead
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,406 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use game::MIN_PLAYERS;
+use player::*;
+use shrink::*;
+use tile::*;
+use tilesheet::*;
+use generator::*;
+
+pub static DEFAULT_ARENA : &'static str = "default";
+const ARENA_DIR : &'static str = "arenas";
+const ARENA_EXTENSION : &'static str = "arena";
+
+const DEFAULT_BORDER : usize = 3;
+const PLAYER_SPAWN_OFFSET : u32 = 235;
+const PLAYER_SPAWN_SIDE_OFFSET : u32 = 153;
+
+//where a robot starts, in pixels
+#[derive(Clone, PartialEq, Debug)]
+pub struct Spawn
+{
+    pub pos_x: u32,
+    pub pos_y: u32,
+    pub dir: Direction,
+}
+
+//the layout a match is played on: which cells have floor, what each tile looks like,
+//where the robots start and how the floor crumbles in a shrinking match.
+//
+//arena files are plain text. blank lines and lines starting with # are ignored, the
+//header is "key: value" lines and the grid follows the "tiles:" line:
+//
+//    size: 14 22
+//    spawn: 238 235 down
+//    shrink_start: 600
+//    shrink_interval: 15
+//    shrink_warning: 90
+//    tiles:
+//    . . . 1 2 3 . . .
+//
+//every grid row has one token per cell, "." for no floor or the tileset image id of the tile,
+//followed by its kind when it is not a plain tile: c cracked, i ice, h heavy, x explosive or
+//one of ^ > v < for a conveyor running that way, e.g. "12x".
+//there is one spawn line per player slot, with a position in pixels and up, down, left or right.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Arena
+{
+    pub name: String,
+    num_tiles_x: usize,
+    num_tiles_y: usize,
+    //image id of the tile in each cell, row by row, None where there is no floor
+    cells: Vec<Option<usize>>,
+    //kind of the tile in each cell, Normal where there is no floor
+    kinds: Vec<TileKind>,
+    pub spawns: Vec<Spawn>,
+    pub shrink: ShrinkSchedule,
+}
+
+fn get_dir_name( dir: Direction ) -> &'static str
+{
+    match dir
+    {
+        Direction::UP => "up",
+        Direction::DOWN => "down",
+        Direction::LEFT => "left",
+        Direction::RIGHT => "right",
+    }
+}
+
+fn parse_dir( name: &str ) -> Option<Direction>
+{
+    match name
+    {
+        "up" => Some( Direction::UP ),
+        "down" => Some( Direction::DOWN ),
+        "left" => Some( Direction::LEFT ),
+        "right" => Some( Direction::RIGHT ),
+        _ => None,
+    }
+}
+
+fn parse_numbers<T: ::std::str::FromStr>( value: &str, count: usize ) -> Option<Vec<T>>
+{
+    let numbers : Vec<T> = value.split_whitespace()
+        .map(|word| word.parse().ok())
+        .collect::<Option<Vec<T>>>()?;
+    if numbers.len() == count { Some( numbers ) } else { None }
+}
+
+//an image id with an optional kind suffix, e.g. "12" or "12x"
+fn parse_cell( token: &str ) -> Option<( usize, TileKind )>
+{
+    let digits = token.find(|c: char| !c.is_ascii_digit()).unwrap_or( token.len() );
+    let image_id = token[..digits].parse().ok()?;
+    let kind = TileKind::from_suffix( &token[digits..] )?;
+    Some( ( image_id, kind ) )
+}
+
+fn invalid_data( message: String ) -> io::Error
+{
+    io::Error::new( io::ErrorKind::InvalidData, message )
+}
+
+impl Arena
+{
+    //the original hand-made arena: the whole tilesheet in order with a 3 tile gap around it
+    pub fn new_default() -> Arena
+    {
+        let sheetmap = SheetMap::new();
+        let num_tiles_x = TILE_SHEET_NUM_ACROSS + DEFAULT_BORDER * 2;
+        let num_tiles_y = TILE_SHEET_NUM_DOWN + DEFAULT_BORDER * 2;
+        let mut cells = Vec::with_capacity( num_tiles_x * num_tiles_y );
+        for y_index in 0..num_tiles_y
+        {
+            for x_index in 0..num_tiles_x
+            {
+                if y_index < DEFAULT_BORDER || y_index >= num_tiles_y - DEFAULT_BORDER || x_index < DEFAULT_BORDER || x_index >= num_tiles_x - DEFAULT_BORDER
+                {
+                    cells.push( None );
+                }
+                else
+                {
+                    cells.push( Some( sheetmap.map[y_index - DEFAULT_BORDER][x_index - DEFAULT_BORDER] ) );
+                }
+            }
+        }
+
+        let width = ( num_tiles_x * TILE_SPACE ) as u32;
+        let height = ( num_tiles_y * TILE_SPACE ) as u32;
+        //top and bottom first so two player matches face off
+        let spawns = vec![
+            Spawn { pos_x: width / 2, pos_y: PLAYER_SPAWN_OFFSET, dir: Direction::DOWN },
+            Spawn { pos_x: width / 2, pos_y: height - PLAYER_SPAWN_OFFSET, dir: Direction::UP },
+            Spawn { pos_x: PLAYER_SPAWN_SIDE_OFFSET, pos_y: height / 2, dir: Direction::RIGHT },
+            Spawn { pos_x: width - PLAYER_SPAWN_SIDE_OFFSET, pos_y: height / 2, dir: Direction::LEFT },
+        ];
+        Arena
+        {
+            name: DEFAULT_ARENA.to_string(),
+            num_tiles_x,
+            num_tiles_y,
+            kinds: vec![TileKind::Normal; cells.len()],
+            cells,
+            spawns,
+            shrink: ShrinkSchedule::default(),
+        }
+    }
+
+    //no floor and no spawns yet, for arenas laid out in code
+    pub fn new_empty( name: &str, num_tiles_x: usize, num_tiles_y: usize ) -> Arena
+    {
+        Arena
+        {
+            name: name.to_string(),
+            num_tiles_x,
+            num_tiles_y,
+            cells: vec![None; num_tiles_x * num_tiles_y],
+            kinds: vec![TileKind::Normal; num_tiles_x * num_tiles_y],
+            spawns: Vec::new(),
+            shrink: ShrinkSchedule::default(),
+        }
+    }
+
+    pub fn get_num_tiles_x( &self ) -> usize
+    {
+        self.num_tiles_x
+    }
+
+    pub fn get_num_tiles_y( &self ) -> usize
+    {
+        self.num_tiles_y
+    }
+
+    pub fn get_width( &self ) -> u32
+    {
+        ( self.num_tiles_x * TILE_SPACE ) as u32
+    }
+
+    pub fn get_height( &self ) -> u32
+    {
+        ( self.num_tiles_y * TILE_SPACE ) as u32
+    }
+
+    pub fn get_cell( &self, x_index: usize, y_index: usize ) -> Option<usize>
+    {
+        self.cells[y_index * self.num_tiles_x + x_index]
+    }
+
+    //clearing a cell also makes it a plain tile again
+    pub fn set_cell( &mut self, x_index: usize, y_index: usize, cell: Option<usize> )
+    {
+        let index = y_index * self.num_tiles_x + x_index;
+        self.cells[index] = cell;
+        if cell.is_none()
+        {
+            self.kinds[index] = TileKind::Normal;
+        }
+    }
+
+    pub fn get_kind( &self, x_index: usize, y_index: usize ) -> TileKind
+    {
+        self.kinds[y_index * self.num_tiles_x + x_index]
+    }
+
+    pub fn set_kind( &mut self, x_index: usize, y_index: usize, kind: TileKind )
+    {
+        self.kinds[y_index * self.num_tiles_x + x_index] = kind;
+    }
+
+    pub fn build_tile_map( &self ) -> TileMap
+    {
+        let map = ( 0..self.num_tiles_y )
+            .map(|y_index| {
+                ( 0..self.num_tiles_x )
+                    .map(|x_index| match self.get_cell( x_index, y_index )
+                    {
+                        Some( image_id ) =>
+                        {
+                            let mut tile = Tile::new( x_index, y_index, image_id );
+                            tile.kind = self.get_kind( x_index, y_index );
+                            tile
+                        }
+                        None => Tile::new_empty( x_index, y_index ),
+                    })
+                    .collect()
+            })
+            .collect();
+        TileMap::new( map )
+    }
+
+    pub fn parse( name: &str, text: &str ) -> Result<Arena, String>
+    {
+        let mut size = None;
+        let mut spawns = Vec::new();
+        let mut shrink = ShrinkSchedule::default();
+        let mut rows : Vec<Vec<Option<( usize, TileKind )>>> = Vec::new();
+        let mut in_tiles = false;
+
+        for ( index, line ) in text.lines().enumerate()
+        {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with( '#' )
+            {
+                continue;
+            }
+            if in_tiles
+            {
+                let row = line.split_whitespace()
+                    .map(|token| match token
+                    {
+                        "." => Ok( None ),
+                        _ => parse_cell( token ).map( Some ).ok_or_else(|| format!( "line {}: '{}' is not a tile", line_number, token )),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                rows.push( row );
+                continue;
+            }
+
+            let ( key, value ) = match line.find( ':' )
+            {
+                Some( colon ) => ( line[..colon].trim(), line[colon + 1..].trim() ),
+                None => return Err( format!( "line {}: expected 'key: value'", line_number ) ),
+            };
+            let bad_value = || format!( "line {}: bad value for {}", line_number, key );
+            match key
+            {
+                "size" =>
+                {
+                    let numbers = parse_numbers::<usize>( value, 2 ).ok_or_else( bad_value )?;
+                    size = Some( ( numbers[0], numbers[1] ) );
+                }
+                "spawn" =>
+                {
+                    let words : Vec<&str> = value.split_whitespace().collect();
+                    if words.len() != 3
+                    {
+                        return Err( bad_value() );
+                    }
+                    let pos = parse_numbers::<u32>( &words[..2].join( " " ), 2 ).ok_or_else( bad_value )?;
+                    let dir = parse_dir( words[2] ).ok_or_else( bad_value )?;
+                    spawns.push( Spawn { pos_x: pos[0], pos_y: pos[1], dir } );
+                }
+                "shrink_start" => { shrink.start_tick = value.parse().map_err(|_| bad_value())?; }
+                "shrink_interval" => { shrink.interval_ticks = value.parse().map_err(|_| bad_value())?; }
+                "shrink_warning" => { shrink.warning_ticks = value.parse().map_err(|_| bad_value())?; }
+                "tiles" => { in_tiles = true; }
+                _ => return Err( format!( "line {}: unknown key '{}'", line_number, key ) ),
+            }
+        }
+
+        let ( num_tiles_x, num_tiles_y ) = size.ok_or( "missing size".to_string() )?;
+        if rows.len() != num_tiles_y || rows.iter().any(|row| row.len() != num_tiles_x)
+        {
+            return Err( format!( "the tiles do not form a {}x{} grid", num_tiles_x, num_tiles_y ) );
+        }
+        let arena = Arena
+        {
+            name: name.to_string(),
+            num_tiles_x,
+            num_tiles_y,
+            cells: rows.iter().flat_map(|row| row.iter().map(|cell| cell.map(|( image_id, _ )| image_id))).collect(),
+            kinds: rows.iter().flat_map(|row| row.iter().map(|cell| cell.map_or( TileKind::Normal, |( _, kind )| kind ))).collect(),
+            spawns,
+            shrink,
+        };
+        arena.validate()?;
+        Ok( arena )
+    }
+
+    //checks everything the simulation relies on, so a bad file fails at load time instead of mid match
+    pub fn validate( &self ) -> Result<(), String>
+    {
+        if self.spawns.len() < MIN_PLAYERS
+        {
+            return Err( format!( "needs at least {} spawns", MIN_PLAYERS ) );
+        }
+        for spawn in &self.spawns
+        {
+            if spawn.pos_x >= self.get_width() || spawn.pos_y >= self.get_height()
+            {
+                return Err( format!( "spawn at {} {} is outside the arena", spawn.pos_x, spawn.pos_y ) );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_text( &self ) -> String
+    {
+        let mut text = format!( "size: {} {}\n", self.num_tiles_x, self.num_tiles_y );
+        for spawn in &self.spawns
+        {
+            text += &format!( "spawn: {} {} {}\n", spawn.pos_x, spawn.pos_y, get_dir_name( spawn.dir ) );
+        }
+        text += &format!( "shrink_start: {}\n", self.shrink.start_tick );
+        text += &format!( "shrink_interval: {}\n", self.shrink.interval_ticks );
+        text += &format!( "shrink_warning: {}\n", self.shrink.warning_ticks );
+        text += "tiles:\n";
+        for y_index in 0..self.num_tiles_y
+        {
+            let row : Vec<String> = ( 0..self.num_tiles_x )
+                .map(|x_index| match self.get_cell( x_index, y_index )
+                {
+                    Some( image_id ) => format!( "{}{}", image_id, self.get_kind( x_index, y_index ).get_suffix() ),
+                    None => ".".to_string(),
+                })
+                .collect();
+            text += &row.join( " " );
+            text += "\n";
+        }
+        text
+    }
+
+    pub fn load( path: &Path ) -> io::Result<Arena>
+    {
+        let mut text = String::new();
+        File::open( path )?.read_to_string( &mut text )?;
+        let name = path.file_stem().map_or( "".to_string(), |stem| stem.to_string_lossy().into_owned() );
+        Arena::parse( &name, &text ).map_err( invalid_data )
+    }
+
+    pub fn save( &self, path: &Path ) -> io::Result<()>
+    {
+        File::create( path )?.write_all( self.to_text().as_bytes() )
+    }
+
+    pub fn get_path( name: &str ) -> PathBuf
+    {
+        Path::new( ARENA_DIR ).join( format!( "{}.{}", name, ARENA_EXTENSION ) )
+    }
+
+    //a path to an arena file, or the name of one in arenas/. the default arena is built in
+    //so the game still runs without the folder, and generated ones are laid out from their seed
+    pub fn find( name_or_path: &str ) -> io::Result<Arena>
+    {
+        let path = Path::new( name_or_path );
+        if path.is_file()
+        {
+            return Arena::load( path );
+        }
+        let path = Arena::get_path( name_or_path );
+        if !path.is_file()
+        {
+            if name_or_path == DEFAULT_ARENA
+            {
+                return Ok( Arena::new_default() );
+            }
+            if let Some( seed ) = parse_generated_name( name_or_path )
+            {
+                return Ok( generate( seed ) );
+            }
+        }
+        Arena::load( &path )
+    }
+
+    //saves under arenas/ by name
+    pub fn save_to_arena_dir( &self ) -> io::Result<PathBuf>
+    {
+        fs::create_dir_all( ARENA_DIR )?;
+        let path = Arena::get_path( &self.name );
+        self.save( &path )?;
+        Ok( path )
+    }
+}
@@ -0,0 +1,503 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use game::*;
+use game_mode::*;
+use player::*;
+use projectile::*;
+use tile::*;
+use anim::*;
+use arena::*;
+use collision::*;
+use rng::*;
+
+pub static CAMPAIGN_SAVE_FILE : &'static str = "campaign.save";
+
+//no robot has id 0, so turret shots hit the robot and never count against a target
+const TURRET_OWNER : u32 = 0;
+//turrets shoot ice, which flies half as far again as a thrown tile and reaches across the arena
+const TURRET_SPRITE_ID : usize = 1;
+
+//what clears a stage
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Goal
+{
+    //knock down every target dummy with a thrown tile
+    HitTargets,
+    //get onto the flag cell, index x and y
+    Reach( usize, usize ),
+    //stay up for this many ticks
+    Survive( u32 ),
+}
+
+//fires a tile at the robot every interval, from a cell usually off the floor
+pub struct Turret
+{
+    pub index_x: usize,
+    pub index_y: usize,
+    pub first_shot_ticks: u32,
+    pub interval_ticks: u32,
+}
+
+//a handcrafted single player challenge on one of the arenas in stages/. those have two spawns like
+//any arena, so they can be played with --arena too, the campaign only uses the first one
+pub struct Stage
+{
+    //progress is saved under this, so it must never change once a stage is out
+    pub key: &'static str,
+    pub name: &'static str,
+    arena: &'static str,
+    pub goal: Goal,
+    pub targets: &'static [( usize, usize )],
+    pub turrets: &'static [Turret],
+    pub shrink: bool,
+}
+
+pub static STAGES : [Stage; 6] = [
+    Stage
+    {
+        key: "targets",
+        name: "Target practice",
+        arena: include_str!( "../stages/targets.arena" ),
+        goal: Goal::HitTargets,
+        targets: &[( 5, 5 ), ( 9, 7 ), ( 4, 11 )],
+        turrets: &[],
+        shrink: false,
+    },
+    Stage
+    {
+        key: "over-the-gap",
+        name: "Over the gap",
+        arena: include_str!( "../stages/over-the-gap.arena" ),
+        goal: Goal::HitTargets,
+        targets: &[( 4, 4 ), ( 9, 4 ), ( 6, 6 ), ( 8, 5 )],
+        turrets: &[],
+        shrink: false,
+    },
+    Stage
+    {
+        key: "mind-the-gap",
+        name: "Mind the gap",
+        arena: include_str!( "../stages/mind-the-gap.arena" ),
+        goal: Goal::Reach( 6, 4 ),
+        targets: &[],
+        turrets: &[],
+        shrink: false,
+    },
+    Stage
+    {
+        key: "under-fire",
+        name: "Under fire",
+        arena: include_str!( "../stages/under-fire.arena" ),
+        goal: Goal::HitTargets,
+        targets: &[( 4, 5 ), ( 9, 5 ), ( 6, 9 ), ( 10, 12 )],
+        turrets: &[Turret { index_x: 1, index_y: 10, first_shot_ticks: 2 * TICKS_PER_SECOND, interval_ticks: 150 }],
+        shrink: false,
+    },
+    Stage
+    {
+        key: "turret-alley",
+        name: "Turret alley",
+        arena: include_str!( "../stages/turret-alley.arena" ),
+        goal: Goal::Survive( 30 * TICKS_PER_SECOND ),
+        targets: &[],
+        turrets: &[
+            Turret { index_x: 1, index_y: 6, first_shot_ticks: 60, interval_ticks: 100 },
+            Turret { index_x: 12, index_y: 15, first_shot_ticks: 110, interval_ticks: 100 },
+        ],
+        shrink: false,
+    },
+    Stage
+    {
+        key: "crossfire",
+        name: "Crossfire",
+        arena: include_str!( "../stages/crossfire.arena" ),
+        goal: Goal::Survive( 45 * TICKS_PER_SECOND ),
+        targets: &[],
+        turrets: &[
+            Turret { index_x: 1, index_y: 5, first_shot_ticks: 60, interval_ticks: 120 },
+            Turret { index_x: 12, index_y: 8, first_shot_ticks: 90, interval_ticks: 120 },
+            Turret { index_x: 1, index_y: 14, first_shot_ticks: 120, interval_ticks: 120 },
+            Turret { index_x: 12, index_y: 17, first_shot_ticks: 150, interval_ticks: 120 },
+        ],
+        shrink: true,
+    },
+];
+
+impl Stage
+{
+    //a one player match that is over as soon as the stage is cleared
+    pub fn get_config( &self, index: usize, seed: u64 ) -> MatchConfig
+    {
+        //the stages are built in, so a broken one can only be a mistake in this file
+        let arena = Arena::parse( self.key, self.arena ).unwrap_or_else(|e| panic!( "stage {} is broken: {}", self.key, e ));
+        let mut config = MatchConfig::with_arena( seed, 1, arena );
+        config.num_players = 1;
+        config.rounds_to_win = 1;
+        config.shrink = self.shrink;
+        config.mode = GameModeKind::Challenge( index );
+        config
+    }
+}
+
+//the game mode of a campaign stage. failing one is a draw, which plays the round again
+#[derive(Clone)]
+pub struct Challenge
+{
+    stage: usize,
+    ticks: u32,
+    //targets still standing
+    targets: Vec<( usize, usize )>,
+    cleared: bool,
+}
+
+impl Challenge
+{
+    pub fn new( stage: usize ) -> Challenge
+    {
+        Challenge
+        {
+            stage,
+            ticks: 0,
+            targets: Vec::new(),
+            cleared: false,
+        }
+    }
+
+    fn get_stage( &self ) -> &'static Stage
+    {
+        &STAGES[self.stage]
+    }
+
+    fn fire_turrets( &self, players: &[Player], projectiles: &mut Vec<Projectile> )
+    {
+        let target = match players.iter().find(|player| player.is_on_floor())
+        {
+            Some( target ) => target,
+            None => return,
+        };
+        for turret in self.get_stage().turrets
+        {
+            if self.ticks < turret.first_shot_ticks || ( self.ticks - turret.first_shot_ticks ) % turret.interval_ticks != 0
+            {
+                continue;
+            }
+            let ( center_x, center_y ) = get_cell_center( turret.index_x, turret.index_y );
+            let dist_x = target.get_pos_x() - center_x;
+            let dist_y = target.get_pos_y() - center_y;
+            let dist = ( dist_x * dist_x + dist_y * dist_y ).sqrt().max( 1.0 );
+            let half_size = TILE_SIZE as f32 / 2.0;
+            projectiles.push( Projectile::new(
+                TURRET_OWNER,
+                center_x - half_size,
+                center_y - half_size,
+                dist_x / dist * THROW_SPEED,
+                dist_y / dist * THROW_SPEED,
+                TURRET_SPRITE_ID,
+                TileKind::Ice ) );
+        }
+    }
+
+    //a target goes down to the first thrown tile that touches it, and takes the tile with it
+    fn hit_targets( &mut self, projectiles: &mut [Projectile], anims: &mut Vec<Anim> )
+    {
+        let mut collider = Collider::circle( ROBOT_RADIUS );
+        self.targets.retain(|&( index_x, index_y )| {
+            let ( center_x, center_y ) = get_cell_center( index_x, index_y );
+            collider.set_pos( center_x, center_y, 0.0 );
+            let hit = projectiles.iter_mut()
+                .find(|projectile| !projectile.is_dead() && projectile.get_owner() != TURRET_OWNER
+                      && collider.touches( projectile.get_collider() ));
+            match hit
+            {
+                Some( projectile ) =>
+                {
+                    projectile.kill();
+                    let prefix = "/collision/collision_animation_".to_owned();
+                    anims.push( Anim::new( center_x - 32.0, center_y - 32.0, prefix, 4, 10 ) );
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+}
+
+impl GameMode for Challenge
+{
+    fn start_round( &mut self, _config: &MatchConfig, _players: &[Player], _tile_map: &TileMap )
+    {
+        self.ticks = 0;
+        self.targets = self.get_stage().targets.to_vec();
+        self.cleared = false;
+    }
+
+    fn update( &mut self, _config: &MatchConfig, players: &mut [Player], _tile_map: &mut TileMap, projectiles: &mut Vec<Projectile>, anims: &mut Vec<Anim>, _rng: &mut Rng )
+    {
+        if self.cleared || players.iter().all(|player| player.is_dead())
+        {
+            return;
+        }
+        self.ticks += 1;
+        self.fire_turrets( players, projectiles );
+        self.cleared = match self.get_stage().goal
+        {
+            Goal::HitTargets =>
+            {
+                self.hit_targets( projectiles, anims );
+                self.targets.is_empty()
+            }
+            Goal::Reach( index_x, index_y ) =>
+            {
+                let ( center_x, center_y ) = get_cell_center( index_x, index_y );
+                let half_size = TILE_SIZE as f32 / 2.0;
+                players.iter().any(|player| {
+                    player.is_on_floor()
+                        && ( player.get_pos_x() - center_x ).abs() <= half_size
+                        && ( player.get_pos_y() - center_y ).abs() <= half_size
+                })
+            }
+            Goal::Survive( ticks ) => self.ticks >= ticks,
+        };
+    }
+
+    fn get_round_outcome( &self, _config: &MatchConfig, players: &[Player] ) -> Outcome
+    {
+        if self.cleared
+        {
+            Outcome::Winner( 1 )
+        }
+        else if players.iter().all(|player| player.is_dead())
+        {
+            Outcome::Draw
+        }
+        else
+        {
+            Outcome::InProgress
+        }
+    }
+
+    fn get_hud_text( &self, _config: &MatchConfig ) -> String
+    {
+        let stage = self.get_stage();
+        let goal = match stage.goal
+        {
+            Goal::HitTargets => format!( "hit the targets, {} left", self.targets.len() ),
+            Goal::Reach( _, _ ) => "reach the flag".to_string(),
+            Goal::Survive( ticks ) => format!( "survive, {}s to go", ( ticks.saturating_sub( self.ticks ) + TICKS_PER_SECOND - 1 ) / TICKS_PER_SECOND ),
+        };
+        format!( "Stage {} of {}: {} - {} - {:.1}s", self.stage + 1, STAGES.len(), stage.name, goal, self.ticks as f32 / TICKS_PER_SECOND as f32 )
+    }
+
+    fn get_markers( &self ) -> Vec<Marker>
+    {
+        let stage = self.get_stage();
+        let mut markers : Vec<Marker> = self.targets.iter()
+            .map(|&( index_x, index_y )| Marker { kind: MarkerKind::Target, index_x, index_y, side: None })
+            .collect();
+        if let Goal::Reach( index_x, index_y ) = stage.goal
+        {
+            markers.push( Marker { kind: MarkerKind::Flag, index_x, index_y, side: None } );
+        }
+        for turret in stage.turrets
+        {
+            markers.push( Marker { kind: MarkerKind::Turret, index_x: turret.index_x, index_y: turret.index_y, side: None } );
+        }
+        markers
+    }
+
+    fn hash( &self, hash: &mut Fnv )
+    {
+        hash.write_u32( self.ticks );
+        hash.write_u32( self.cleared as u32 );
+        for &( index_x, index_y ) in &self.targets
+        {
+            hash.write_u32( index_x as u32 );
+            hash.write_u32( index_y as u32 );
+        }
+    }
+
+    fn box_clone( &self ) -> Box<dyn GameMode>
+    {
+        Box::new( self.clone() )
+    }
+}
+
+fn invalid_data( message: String ) -> io::Error
+{
+    io::Error::new( io::ErrorKind::InvalidData, message )
+}
+
+//the best time of every cleared stage, in ticks and indexed like STAGES. a stage is open once
+//the one before it is cleared
+#[derive(Clone, PartialEq, Debug)]
+pub struct CampaignSave
+{
+    best_ticks: Vec<Option<u64>>,
+}
+
+impl Default for CampaignSave
+{
+    fn default() -> CampaignSave
+    {
+        CampaignSave { best_ticks: vec![None; STAGES.len()] }
+    }
+}
+
+impl CampaignSave
+{
+    //"key: ticks" lines, stages this build doesn't know are dropped
+    pub fn parse( text: &str ) -> Result<CampaignSave, String>
+    {
+        let mut save = CampaignSave::default();
+        for ( index, line ) in text.lines().enumerate()
+        {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with( '#' )
+            {
+                continue;
+            }
+            let colon = line.find( ':' ).ok_or_else(|| format!( "line {}: expected 'stage: ticks'", line_number ))?;
+            let ( key, value ) = ( line[..colon].trim(), line[colon + 1..].trim() );
+            let ticks = value.parse().map_err(|_| format!( "line {}: bad time for {}", line_number, key ))?;
+            if let Some( stage ) = STAGES.iter().position(|stage| stage.key == key)
+            {
+                save.best_ticks[stage] = Some( ticks );
+            }
+        }
+        Ok( save )
+    }
+
+    pub fn to_text( &self ) -> String
+    {
+        let mut text = "# best time of every cleared stage, in 1/60ths of a second\n".to_string();
+        for ( stage, best ) in STAGES.iter().zip( &self.best_ticks )
+        {
+            if let Some( ticks ) = *best
+            {
+                text += &format!( "{}: {}\n", stage.key, ticks );
+            }
+        }
+        text
+    }
+
+    pub fn load( path: &Path ) -> io::Result<CampaignSave>
+    {
+        let mut text = String::new();
+        File::open( path )?.read_to_string( &mut text )?;
+        CampaignSave::parse( &text ).map_err( invalid_data )
+    }
+
+    pub fn save( &self, path: &Path ) -> io::Result<()>
+    {
+        File::create( path )?.write_all( self.to_text().as_bytes() )
+    }
+
+    //the save file if there is one, a fresh campaign otherwise
+    pub fn load_or_default() -> io::Result<CampaignSave>
+    {
+        let path = Path::new( CAMPAIGN_SAVE_FILE );
+        if !path.is_file()
+        {
+            return Ok( CampaignSave::default() );
+        }
+        CampaignSave::load( path )
+    }
+
+    pub fn get_best_ticks( &self, stage: usize ) -> Option<u64>
+    {
+        self.best_ticks[stage]
+    }
+
+    pub fn is_unlocked( &self, stage: usize ) -> bool
+    {
+        stage < STAGES.len() && ( stage == 0 || self.best_ticks[stage - 1].is_some() )
+    }
+
+    //the first stage still to clear, or the last one once they all are
+    pub fn get_next_stage( &self ) -> usize
+    {
+        self.best_ticks.iter().position(|best| best.is_none()).unwrap_or( STAGES.len() - 1 )
+    }
+
+    //true when the time beats the best one so far
+    pub fn record( &mut self, stage: usize, ticks: u64 ) -> bool
+    {
+        let best = self.best_ticks[stage].map_or( true, |best| ticks < best );
+        if best
+        {
+            self.best_ticks[stage] = Some( ticks );
+        }
+        best
+    }
+}
+
+//a campaign being played: the progress so far and the stage on now
+pub struct Campaign
+{
+    save: CampaignSave,
+    stage: usize,
+    //the current attempt's time has gone into the save
+    recorded: bool,
+}
+
+impl Campaign
+{
+    pub fn new( save: CampaignSave, stage: usize ) -> Campaign
+    {
+        Campaign { save, stage, recorded: false }
+    }
+
+    pub fn get_config( &self, seed: u64 ) -> MatchConfig
+    {
+        STAGES[self.stage].get_config( self.stage, seed )
+    }
+
+    //keeps the time once the stage is cleared, saved straight away so quitting loses nothing
+    pub fn update( &mut self, game: &Game )
+    {
+        if self.recorded || game.get_round_result() != Some( Outcome::Winner( 1 ) )
+        {
+            return;
+        }
+        self.recorded = true;
+        let ticks = game.get_round_ticks();
+        if self.save.record( self.stage, ticks )
+        {
+            println!( "New best time for {}: {:.1}s", STAGES[self.stage].name, ticks as f32 / TICKS_PER_SECOND as f32 );
+        }
+        if let Err( e ) = self.save.save( Path::new( CAMPAIGN_SAVE_FILE ) )
+        {
+            println!( "Could not save the campaign: {}", e );
+        }
+    }
+
+    //on to the next stage after clearing this one, otherwise this one again
+    pub fn next( &mut self )
+    {
+        if self.recorded && self.save.is_unlocked( self.stage + 1 )
+        {
+            self.stage += 1;
+        }
+        self.recorded = false;
+    }
+
+    pub fn get_status_text( &self, game: &Game ) -> Option<String>
+    {
+        let name = STAGES[self.stage].name;
+        match game.get_round_result()
+        {
+            Some( Outcome::Winner( _ ) ) =>
+            {
+                let time = game.get_round_ticks() as f32 / TICKS_PER_SECOND as f32;
+                let best = self.save.get_best_ticks( self.stage ).unwrap_or( 0 ) as f32 / TICKS_PER_SECOND as f32;
+                let next = if self.stage + 1 < STAGES.len() { "F5 for the next stage." } else { "That was the last one, F5 to play it again." };
+                Some( format!( "{} cleared in {:.1}s, best {:.1}s. {}", name, time, best, next ) )
+            }
+            Some( _ ) => Some( format!( "{} failed, try again!", name ) ),
+            None => None,
+        }
+    }
+}
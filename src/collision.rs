@@ -0,0 +1,86 @@
+use ncollide2d::bounding_volume::{self, BoundingVolume, AABB};
+use ncollide2d::math::{Isometry, Vector};
+use ncollide2d::query;
+use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
+
+use std::cmp::Ordering;
+
+//a shape placed in the arena, positioned by its centre in pixels
+#[derive(Clone)]
+pub struct Collider
+{
+    shape: ShapeHandle<f32>,
+    pos: Isometry<f32>,
+}
+
+impl Collider
+{
+    pub fn circle( radius: f32 ) -> Collider
+    {
+        Collider
+        {
+            shape: ShapeHandle::new( Ball::new( radius ) ),
+            pos: Isometry::identity(),
+        }
+    }
+
+    pub fn rect( width: f32, height: f32 ) -> Collider
+    {
+        Collider
+        {
+            shape: ShapeHandle::new( Cuboid::new( Vector::new( width / 2.0, height / 2.0 ) ) ),
+            pos: Isometry::identity(),
+        }
+    }
+
+    //angle is in radians, clockwise on screen
+    pub fn set_pos( &mut self, pos_x: f32, pos_y: f32, angle: f32 )
+    {
+        self.pos = Isometry::new( Vector::new( pos_x, pos_y ), angle );
+    }
+
+    fn get_aabb( &self ) -> AABB<f32>
+    {
+        bounding_volume::aabb( self.shape.as_ref(), &self.pos )
+    }
+
+    //whether the two shapes overlap, merely touching edges don't count
+    pub fn touches( &self, other: &Collider ) -> bool
+    {
+        query::contact( &self.pos, self.shape.as_ref(), &other.pos, other.shape.as_ref(), 0.0 )
+            .map_or( false, |contact| contact.depth > 0.0 )
+    }
+}
+
+//broad phase, sort and sweep along x: every pair of a collider from first and one from second
+//whose bounding boxes overlap, as indices into the two slices and in index order so whoever
+//handles them always does so in the same order
+pub fn get_overlapping_pairs( first: &[&Collider], second: &[&Collider] ) -> Vec<( usize, usize )>
+{
+    let mut entries : Vec<( AABB<f32>, bool, usize )> = first.iter()
+        .enumerate()
+        .map(|( index, collider )| ( collider.get_aabb(), false, index ))
+        .chain( second.iter().enumerate().map(|( index, collider )| ( collider.get_aabb(), true, index )) )
+        .collect();
+    entries.sort_by(|a, b| {
+        a.0.mins().x.partial_cmp( &b.0.mins().x ).unwrap_or( Ordering::Equal )
+            .then( ( a.1, a.2 ).cmp( &( b.1, b.2 ) ) )
+    });
+
+    let mut pairs = Vec::new();
+    let mut active : Vec<&( AABB<f32>, bool, usize )> = Vec::new();
+    for entry in &entries
+    {
+        active.retain(|other| other.0.maxs().x >= entry.0.mins().x);
+        for other in &active
+        {
+            if other.1 != entry.1 && other.0.intersects( &entry.0 )
+            {
+                pairs.push( if entry.1 { ( other.2, entry.2 ) } else { ( entry.2, other.2 ) } );
+            }
+        }
+        active.push( entry );
+    }
+    pairs.sort();
+    pairs
+}
@@ -0,0 +1,293 @@
+use ggez::event::Keycode;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use game::*;
+
+pub static CONTROLS_FILE : &'static str = "controls.cfg";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action
+{
+    Up,
+    Down,
+    Left,
+    Right,
+    Action,
+}
+
+pub const ACTIONS : [Action; 5] = [Action::Up, Action::Down, Action::Left, Action::Right, Action::Action];
+
+impl Action
+{
+    fn get_name( &self ) -> &'static str
+    {
+        match *self
+        {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Action => "action",
+        }
+    }
+
+    fn from_name( name: &str ) -> Option<Action>
+    {
+        ACTIONS.iter().cloned().find(|action| action.get_name() == name)
+    }
+}
+
+//the keys of one player slot
+#[derive(Clone, PartialEq, Debug)]
+pub struct Binding
+{
+    pub up: Keycode,
+    pub down: Keycode,
+    pub left: Keycode,
+    pub right: Keycode,
+    pub action: Keycode,
+}
+
+impl Binding
+{
+    pub fn get( &self, action: Action ) -> Keycode
+    {
+        match action
+        {
+            Action::Up => self.up,
+            Action::Down => self.down,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::Action => self.action,
+        }
+    }
+
+    pub fn set( &mut self, action: Action, keycode: Keycode )
+    {
+        match action
+        {
+            Action::Up => { self.up = keycode }
+            Action::Down => { self.down = keycode }
+            Action::Left => { self.left = keycode }
+            Action::Right => { self.right = keycode }
+            Action::Action => { self.action = keycode }
+        }
+    }
+
+    //"WASD + Space" when every direction is a single letter, spelled out with slashes otherwise
+    pub fn get_help( &self ) -> String
+    {
+        let dirs = [self.up.name(), self.left.name(), self.down.name(), self.right.name()];
+        let separator = if dirs.iter().all(|name| name.chars().count() == 1) { "" } else { "/" };
+        format!( "{} + {}", dirs.join( separator ), self.action.name() )
+    }
+}
+
+//every player slot's keys, read from and written back to a plain text file of
+//"p<slot>.<action>: <key name>" lines, e.g. "p1.up: W"
+#[derive(Clone, PartialEq, Debug)]
+pub struct Controls
+{
+    pub bindings: Vec<Binding>,
+}
+
+impl Default for Controls
+{
+    fn default() -> Controls
+    {
+        Controls
+        {
+            bindings: vec![
+                Binding { up: Keycode::W, down: Keycode::S, left: Keycode::A, right: Keycode::D, action: Keycode::Space },
+                Binding { up: Keycode::Up, down: Keycode::Down, left: Keycode::Left, right: Keycode::Right, action: Keycode::Return },
+                Binding { up: Keycode::I, down: Keycode::K, left: Keycode::J, right: Keycode::L, action: Keycode::O },
+                Binding { up: Keycode::Kp8, down: Keycode::Kp5, left: Keycode::Kp4, right: Keycode::Kp6, action: Keycode::Kp0 },
+            ],
+        }
+    }
+}
+
+fn invalid_data( message: String ) -> io::Error
+{
+    io::Error::new( io::ErrorKind::InvalidData, message )
+}
+
+impl Controls
+{
+    //the player slot and action a key is bound to, if any
+    pub fn find( &self, keycode: Keycode ) -> Option<( usize, Action )>
+    {
+        for ( index, binding ) in self.bindings.iter().enumerate()
+        {
+            for action in ACTIONS.iter()
+            {
+                if binding.get( *action ) == keycode
+                {
+                    return Some( ( index, *action ) );
+                }
+            }
+        }
+        None
+    }
+
+    //lines that are missing keep their default, so older files keep working
+    pub fn parse( text: &str ) -> Result<Controls, String>
+    {
+        let mut controls = Controls::default();
+        for ( index, line ) in text.lines().enumerate()
+        {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with( '#' )
+            {
+                continue;
+            }
+            let bad_line = || format!( "line {}: expected 'p<slot>.<action>: <key>'", line_number );
+            let colon = line.find( ':' ).ok_or_else( bad_line )?;
+            let ( name, key ) = ( line[..colon].trim(), line[colon + 1..].trim() );
+            let dot = name.find( '.' ).ok_or_else( bad_line )?;
+            if !name.starts_with( 'p' )
+            {
+                return Err( bad_line() );
+            }
+            let slot : usize = name[1..dot].parse().map_err(|_| bad_line())?;
+            if slot == 0 || slot > MAX_PLAYERS
+            {
+                return Err( format!( "line {}: there is no player {}", line_number, slot ) );
+            }
+            let action = Action::from_name( &name[dot + 1..] )
+                .ok_or_else(|| format!( "line {}: unknown action '{}'", line_number, &name[dot + 1..] ))?;
+            let keycode = Keycode::from_name( key )
+                .ok_or_else(|| format!( "line {}: unknown key '{}'", line_number, key ))?;
+            controls.bindings[slot - 1].set( action, keycode );
+        }
+        Ok( controls )
+    }
+
+    pub fn to_text( &self ) -> String
+    {
+        let mut text = "# one line per player and action, key names as SDL spells them\n".to_string();
+        for ( index, binding ) in self.bindings.iter().enumerate()
+        {
+            for action in ACTIONS.iter()
+            {
+                text += &format!( "p{}.{}: {}\n", index + 1, action.get_name(), binding.get( *action ).name() );
+            }
+        }
+        text
+    }
+
+    pub fn load( path: &Path ) -> io::Result<Controls>
+    {
+        let mut text = String::new();
+        File::open( path )?.read_to_string( &mut text )?;
+        Controls::parse( &text ).map_err( invalid_data )
+    }
+
+    pub fn save( &self, path: &Path ) -> io::Result<()>
+    {
+        File::create( path )?.write_all( self.to_text().as_bytes() )
+    }
+
+    //the controls file if there is one, the defaults otherwise
+    pub fn load_or_default() -> io::Result<Controls>
+    {
+        let path = Path::new( CONTROLS_FILE );
+        if !path.is_file()
+        {
+            return Ok( Controls::default() );
+        }
+        Controls::load( path )
+    }
+}
+
+//the rebinding screen: pick a player, then press a key for each of their actions in turn
+pub struct Rebind
+{
+    controls: Controls,
+    slot: Option<usize>,
+    step: usize,
+    problem: Option<String>,
+}
+
+impl Rebind
+{
+    pub fn new( controls: Controls ) -> Rebind
+    {
+        Rebind
+        {
+            controls,
+            slot: None,
+            step: 0,
+            problem: None,
+        }
+    }
+
+    pub fn get_controls( &self ) -> &Controls
+    {
+        &self.controls
+    }
+
+    pub fn get_prompt( &self ) -> String
+    {
+        let prompt = match self.slot
+        {
+            None => "Rebind controls: press 1-4 to pick a player, Escape to leave".to_string(),
+            Some( slot ) => format!( "P{}: press the key for {}, Backspace keeps {}",
+                                     slot + 1, ACTIONS[self.step].get_name(), self.controls.bindings[slot].get( ACTIONS[self.step] ).name() ),
+        };
+        match self.problem
+        {
+            Some( ref problem ) => format!( "{} - {}", problem, prompt ),
+            None => prompt,
+        }
+    }
+
+    //true once a player's keys are all set and should be saved
+    pub fn key_down( &mut self, keycode: Keycode ) -> bool
+    {
+        self.problem = None;
+        let slot = match self.slot
+        {
+            Some( slot ) => slot,
+            None =>
+            {
+                let slot = match keycode
+                {
+                    Keycode::Num1 | Keycode::Kp1 => 0,
+                    Keycode::Num2 | Keycode::Kp2 => 1,
+                    Keycode::Num3 | Keycode::Kp3 => 2,
+                    Keycode::Num4 | Keycode::Kp4 => 3,
+                    _ => return false,
+                };
+                self.slot = Some( slot );
+                self.step = 0;
+                return false;
+            }
+        };
+
+        let action = ACTIONS[self.step];
+        if keycode != Keycode::Backspace
+        {
+            match self.controls.find( keycode )
+            {
+                Some( ( other_slot, other_action ) ) if ( other_slot, other_action ) != ( slot, action ) =>
+                {
+                    self.problem = Some( format!( "{} is already P{} {}", keycode.name(), other_slot + 1, other_action.get_name() ) );
+                    return false;
+                }
+                _ => { self.controls.bindings[slot].set( action, keycode ); }
+            }
+        }
+        self.step += 1;
+        if self.step < ACTIONS.len()
+        {
+            return false;
+        }
+        self.slot = None;
+        true
+    }
+}
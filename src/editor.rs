@@ -0,0 +1,278 @@
+use std::io;
+use std::path::PathBuf;
+
+use arena::*;
+use game::*;
+use player::*;
+use tile::*;
+use tilesheet::*;
+
+const NUM_BRUSHES : usize = TILE_SHEET_NUM_ACROSS * TILE_SHEET_NUM_DOWN;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tool
+{
+    //click to add or remove floor, new floor uses the brush
+    Floor,
+    //click floor to give it the brush's image
+    Paint,
+    //click to move the selected player slot's spawn there
+    Spawn,
+    //click floor to turn it into the next kind of tile
+    Kind,
+}
+
+//an arena being edited. works on cell indices and pixel positions, the window events are
+//turned into these calls by MainState
+pub struct Editor
+{
+    arena: Arena,
+    tool: Tool,
+    brush: usize,
+    spawn_slot: usize,
+    cursor: Option<( usize, usize )>,
+    //what a floor drag is turning cells into, so dragging over a mix doesn't flip them back and forth
+    dragging: Option<bool>,
+    saved: bool,
+}
+
+impl Editor
+{
+    pub fn new( arena: Arena ) -> Editor
+    {
+        Editor
+        {
+            arena,
+            tool: Tool::Floor,
+            brush: 1,
+            spawn_slot: 0,
+            cursor: None,
+            dragging: None,
+            saved: true,
+        }
+    }
+
+    pub fn get_arena( &self ) -> &Arena
+    {
+        &self.arena
+    }
+
+    pub fn get_tool( &self ) -> Tool
+    {
+        self.tool
+    }
+
+    pub fn set_tool( &mut self, tool: Tool )
+    {
+        self.tool = tool;
+    }
+
+    pub fn get_brush( &self ) -> usize
+    {
+        self.brush
+    }
+
+    pub fn next_brush( &mut self, step: i32 )
+    {
+        let brush = ( self.brush as i32 - 1 + step ).rem_euclid( NUM_BRUSHES as i32 );
+        self.brush = brush as usize + 1;
+    }
+
+    pub fn get_spawn_slot( &self ) -> usize
+    {
+        self.spawn_slot
+    }
+
+    //cycles through the arena's spawns plus one more, until every player slot has one
+    pub fn next_spawn_slot( &mut self )
+    {
+        let num_slots = ( self.arena.spawns.len() + 1 ).min( MAX_PLAYERS );
+        self.spawn_slot = ( self.spawn_slot + 1 ) % num_slots;
+    }
+
+    pub fn get_cursor( &self ) -> Option<( usize, usize )>
+    {
+        self.cursor
+    }
+
+    pub fn is_saved( &self ) -> bool
+    {
+        self.saved
+    }
+
+    fn get_cell_index( &self, pos_x: i32, pos_y: i32 ) -> Option<( usize, usize )>
+    {
+        if pos_x < 0 || pos_y < 0
+        {
+            return None;
+        }
+        let x_index = pos_x as usize / TILE_SPACE;
+        let y_index = pos_y as usize / TILE_SPACE;
+        if x_index < self.arena.get_num_tiles_x() && y_index < self.arena.get_num_tiles_y()
+        {
+            Some( ( x_index, y_index ) )
+        }
+        else
+        {
+            None
+        }
+    }
+
+    pub fn move_cursor( &mut self, pos_x: i32, pos_y: i32 )
+    {
+        self.cursor = self.get_cell_index( pos_x, pos_y );
+        if let Some( ( x_index, y_index ) ) = self.cursor
+        {
+            match ( self.tool, self.dragging )
+            {
+                ( Tool::Floor, Some( full ) ) => self.set_floor( x_index, y_index, full ),
+                ( Tool::Paint, Some( _ ) ) => self.paint( x_index, y_index ),
+                _ => {}
+            }
+        }
+    }
+
+    //the main button went down at a pixel position
+    pub fn press( &mut self, pos_x: i32, pos_y: i32 )
+    {
+        let ( x_index, y_index ) = match self.get_cell_index( pos_x, pos_y )
+        {
+            Some( cell ) => cell,
+            None => return,
+        };
+        match self.tool
+        {
+            Tool::Floor =>
+            {
+                let full = self.arena.get_cell( x_index, y_index ).is_none();
+                self.dragging = Some( full );
+                self.set_floor( x_index, y_index, full );
+            }
+            Tool::Paint =>
+            {
+                self.dragging = Some( true );
+                self.paint( x_index, y_index );
+            }
+            Tool::Spawn =>
+            {
+                self.place_spawn( x_index, y_index );
+            }
+            Tool::Kind =>
+            {
+                self.next_kind( x_index, y_index );
+            }
+        }
+    }
+
+    pub fn release( &mut self )
+    {
+        self.dragging = None;
+    }
+
+    //takes the brush from the tile under the cursor
+    pub fn pick( &mut self, pos_x: i32, pos_y: i32 )
+    {
+        if let Some( ( x_index, y_index ) ) = self.get_cell_index( pos_x, pos_y )
+        {
+            if let Some( image_id ) = self.arena.get_cell( x_index, y_index )
+            {
+                self.brush = image_id;
+            }
+        }
+    }
+
+    fn set_floor( &mut self, x_index: usize, y_index: usize, full: bool )
+    {
+        let cell = self.arena.get_cell( x_index, y_index );
+        if cell.is_some() == full
+        {
+            return;
+        }
+        self.arena.set_cell( x_index, y_index, if full { Some( self.brush ) } else { None } );
+        self.saved = false;
+    }
+
+    fn paint( &mut self, x_index: usize, y_index: usize )
+    {
+        if let Some( image_id ) = self.arena.get_cell( x_index, y_index )
+        {
+            if image_id != self.brush
+            {
+                self.arena.set_cell( x_index, y_index, Some( self.brush ) );
+                self.saved = false;
+            }
+        }
+    }
+
+    fn next_kind( &mut self, x_index: usize, y_index: usize )
+    {
+        if self.arena.get_cell( x_index, y_index ).is_some()
+        {
+            let kind = self.arena.get_kind( x_index, y_index ).next();
+            self.arena.set_kind( x_index, y_index, kind );
+            self.saved = false;
+        }
+    }
+
+    fn place_spawn( &mut self, x_index: usize, y_index: usize )
+    {
+        let slot = self.spawn_slot;
+        let spawn = Spawn
+        {
+            pos_x: ( x_index * TILE_SPACE + TILE_SPACE / 2 ) as u32,
+            pos_y: ( y_index * TILE_SPACE + TILE_SPACE / 2 ) as u32,
+            //keep the old facing when moving a spawn, new ones face the middle
+            dir: match self.arena.spawns.get( slot )
+            {
+                Some( old ) => old.dir,
+                None => self.get_dir_to_center( x_index, y_index ),
+            },
+        };
+        if slot < self.arena.spawns.len()
+        {
+            self.arena.spawns[slot] = spawn;
+        }
+        else
+        {
+            self.arena.spawns.push( spawn );
+        }
+        self.saved = false;
+    }
+
+    //turns the selected spawn a quarter clockwise
+    pub fn rotate_spawn( &mut self )
+    {
+        if let Some( spawn ) = self.arena.spawns.get_mut( self.spawn_slot )
+        {
+            spawn.dir = match spawn.dir
+            {
+                Direction::UP => Direction::RIGHT,
+                Direction::RIGHT => Direction::DOWN,
+                Direction::DOWN => Direction::LEFT,
+                Direction::LEFT => Direction::UP,
+            };
+            self.saved = false;
+        }
+    }
+
+    fn get_dir_to_center( &self, x_index: usize, y_index: usize ) -> Direction
+    {
+        let dx = x_index as i32 * 2 - self.arena.get_num_tiles_x() as i32;
+        let dy = y_index as i32 * 2 - self.arena.get_num_tiles_y() as i32;
+        if dx.abs() > dy.abs()
+        {
+            if dx > 0 { Direction::LEFT } else { Direction::RIGHT }
+        }
+        else
+        {
+            if dy > 0 { Direction::UP } else { Direction::DOWN }
+        }
+    }
+
+    pub fn save( &mut self ) -> io::Result<PathBuf>
+    {
+        let path = self.arena.save_to_arena_dir()?;
+        self.saved = true;
+        Ok( path )
+    }
+}
@@ -0,0 +1,597 @@
+use player::*;
+use tile::*;
+use projectile::*;
+use anim::*;
+use input::*;
+use shrink::*;
+use arena::*;
+use collision::*;
+use physics::*;
+use powerup::*;
+use game_mode::*;
+use rng::*;
+
+use std::cmp::Ordering;
+
+pub const TICKS_PER_SECOND : u32 = 60;
+pub const PLAYER_SPEED : f32 = 2.0;
+pub const MIN_PLAYERS : usize = 2;
+pub const MAX_PLAYERS : usize = 4;
+pub const DEFAULT_ROUNDS_TO_WIN : u32 = 2;
+pub const MAX_ROUNDS_TO_WIN : u32 = 255;
+pub const MAX_HIT_POINTS : u32 = 255;
+pub const MAX_LIVES : u32 = 255;
+//odd players against even ones, which matches the red and green robots
+pub const NUM_TEAMS : usize = 2;
+//how long the result of a round stays up before the next one starts
+pub const ROUND_DELAY_TICKS : u64 = 3 * TICKS_PER_SECOND as u64;
+
+//winners are player ids, or team numbers when playing in teams
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome
+{
+    InProgress,
+    Winner(u32),
+    Draw,
+}
+
+//everything needed to start an identical match again
+#[derive(Clone, PartialEq, Debug)]
+pub struct MatchConfig
+{
+    pub seed: u64,
+    pub arena: Arena,
+    pub num_players: usize,
+    //the floor crumbles from the outside in on the arena's schedule
+    pub shrink: bool,
+    //the first player to win this many rounds wins the match
+    pub rounds_to_win: u32,
+    //a hit knocks the robot back with the tile's momentum instead of taking it out
+    pub knockback: bool,
+    //thrown tiles bounce off the edges of the arena instead of flying off
+    pub ricochet: bool,
+    pub powerups: PowerupConfig,
+    //tile hits a robot takes before it is knocked out, 1 is the classic one hit and out
+    pub hit_points: u32,
+    //times a robot comes back at its spawn point after falling or being knocked out, plus one
+    pub lives: u32,
+    //players 1 and 3 against 2 and 4, rounds and the match are won together
+    pub teams: bool,
+    //teammates' tiles and shoves hit as hard as anyone else's
+    pub friendly_fire: bool,
+    //what it takes to win a round
+    pub mode: GameModeKind,
+}
+
+impl MatchConfig
+{
+    pub fn new( seed: u64, num_players: usize ) -> MatchConfig
+    {
+        MatchConfig::with_arena( seed, num_players, Arena::new_default() )
+    }
+
+    //there are never more players than the arena has spawns for
+    pub fn with_arena( seed: u64, num_players: usize, arena: Arena ) -> MatchConfig
+    {
+        MatchConfig
+        {
+            seed,
+            num_players: num_players.max( MIN_PLAYERS ).min( MAX_PLAYERS ).min( arena.spawns.len() ),
+            arena,
+            shrink: false,
+            rounds_to_win: DEFAULT_ROUNDS_TO_WIN,
+            knockback: false,
+            ricochet: false,
+            powerups: PowerupConfig::default(),
+            hit_points: 1,
+            lives: 1,
+            teams: false,
+            friendly_fire: false,
+            mode: GameModeKind::LastStanding,
+        }
+    }
+
+    //without teams everyone is on a team of their own, numbered like the player. id 0 is no player
+    //(the campaign turrets) and stays on team 0 against everyone
+    pub fn get_team( &self, id: u32 ) -> u32
+    {
+        if self.teams && id > 0
+        {
+            ( id - 1 ) % NUM_TEAMS as u32 + 1
+        }
+        else
+        {
+            id
+        }
+    }
+
+    //who scores rounds, the teams or each player
+    pub fn get_num_sides( &self ) -> usize
+    {
+        if self.teams
+        {
+            self.num_players.min( NUM_TEAMS )
+        }
+        else
+        {
+            self.num_players
+        }
+    }
+
+    //how sides are shown on the scoreboard, P1 or T1
+    pub fn get_side_prefix( &self ) -> &'static str
+    {
+        if self.teams { "T" } else { "P" }
+    }
+
+    //whether a tile thrown or a shove made by one player counts against another
+    pub fn can_hit( &self, attacker: u32, target: u32 ) -> bool
+    {
+        attacker != target && ( self.friendly_fire || self.get_team( attacker ) != self.get_team( target ) )
+    }
+}
+
+//the whole state of a match, advanced in fixed ticks without any window or graphics context.
+//rounds follow each other on their own, so replays and online matches cover the whole match
+#[derive(Clone)]
+pub struct Game
+{
+    config: MatchConfig,
+    tick: u64,
+    round: u32,
+    round_start_tick: u64,
+    //when and how the current round was decided, the next one starts ROUND_DELAY_TICKS later
+    round_over: Option<( u64, Outcome )>,
+    //rounds won, indexed by player id - 1, or team - 1 when playing in teams
+    scores: Vec<u32>,
+    held: Vec<PlayerInput>,
+    pub players: Vec<Player>,
+    pub tile_map: TileMap,
+    pub projectiles: Vec<Projectile>,
+    pub anims: Vec<Anim>,
+    shrink: Option<Shrink>,
+    spawner: PowerupSpawner,
+    mode: Box<dyn GameMode>,
+    //every random number of the match comes from here, the streams carry on from round to round
+    rng: GameRng,
+}
+
+impl Game
+{
+    pub fn new( config: MatchConfig ) -> Game
+    {
+        let tile_map = config.arena.build_tile_map();
+        let mut game = Game
+        {
+            held: Vec::new(),
+            scores: vec![0; config.get_num_sides()],
+            mode: config.mode.create(),
+            rng: GameRng::new( config.seed ),
+            config,
+            tick: 0,
+            round: 0,
+            round_start_tick: 0,
+            round_over: None,
+            players: Vec::new(),
+            tile_map,
+            projectiles: Vec::new(),
+            anims: Vec::new(),
+            shrink: None,
+            spawner: PowerupSpawner::new( PowerupConfig::default() ),
+        };
+        game.start_round();
+        game
+    }
+
+    //puts the arena and the robots back the way the match started, the scores are kept
+    fn start_round( &mut self )
+    {
+        let config = &self.config;
+        self.round += 1;
+        self.round_start_tick = self.tick;
+        self.round_over = None;
+        self.players = config.arena.spawns.iter()
+            .take( config.num_players )
+            .enumerate()
+            .map(|( index, spawn )| {
+                let mut player = Player::new( index as u32 + 1, spawn.pos_x, spawn.pos_y, spawn.dir );
+                player.set_health( config.hit_points, config.lives );
+                player
+            })
+            .collect();
+        self.held = vec![PlayerInput::default(); config.num_players];
+        self.tile_map = config.arena.build_tile_map();
+        self.projectiles.clear();
+        self.anims.clear();
+        self.spawner = PowerupSpawner::new( config.powerups.clone() );
+        self.mode.start_round( config, &self.players, &self.tile_map );
+        self.shrink = if config.shrink || self.mode.always_shrinks()
+        {
+            Some( Shrink::new( config.arena.shrink.clone(), &self.tile_map ) )
+        }
+        else
+        {
+            None
+        };
+    }
+
+    //advances the match by exactly one 1/TICKS_PER_SECOND step, inputs are indexed by player id - 1
+    pub fn tick( &mut self, inputs: &[PlayerInput] )
+    {
+        self.tick += 1;
+        for ( index, input ) in inputs.iter().enumerate().take( self.players.len() )
+        {
+            self.apply_input( index, *input );
+        }
+
+        //tiles fall before anyone moves, so a robot standing on one drops this very tick
+        if let Some( ref mut shrink ) = self.shrink
+        {
+            shrink.update( self.tick - self.round_start_tick, &mut self.tile_map );
+        }
+        self.tile_map.update();
+
+        for player in &mut self.players
+        {
+            player.prepare_move( &self.tile_map );
+        }
+        let hits = {
+            let config = &self.config;
+            step_bodies( &mut self.players, &self.projectiles, &|player, projectile| config.can_hit( projectile.get_owner(), player.get_id() ) )
+        };
+        for player in &mut self.players
+        {
+            player.update( &self.tile_map );
+            if player.is_on_floor()
+            {
+                self.tile_map.step_on( player.get_pos_x(), player.get_pos_y() );
+            }
+        }
+        self.update_powerups();
+        self.handle_hits( &hits );
+        self.mode.update( &self.config, &mut self.players, &mut self.tile_map, &mut self.projectiles, &mut self.anims, self.rng.get( RngStream::GameMode ) );
+        let width = ( self.tile_map.get_num_tiles_x() * TILE_SPACE ) as f32;
+        let height = ( self.tile_map.get_num_tiles_y() * TILE_SPACE ) as f32;
+        for ref mut projectile in &mut self.projectiles
+        {
+            projectile.update( &mut self.tile_map, &mut self.anims );
+            if self.config.ricochet
+            {
+                projectile.bounce_off_walls( width, height );
+            }
+        }
+        self.projectiles.retain(|projectile| {
+            !projectile.is_gone() && !projectile.is_out_of_bounds( width, height )
+        });
+        for ref mut anim in &mut self.anims
+        {
+            anim.update( );
+        }
+
+        self.anims.retain(|anim| {!anim.is_dead()});
+
+        self.update_round();
+    }
+
+    //new power-ups turn up between the robots, and robots walking over one collect it
+    fn update_powerups( &mut self )
+    {
+        let occupied : Vec<( f32, f32 )> = self.players.iter()
+            .filter(|player| !player.is_dead())
+            .map(|player| ( player.get_pos_x(), player.get_pos_y() ))
+            .collect();
+        self.spawner.update( &self.tile_map, &occupied, self.rng.get( RngStream::Powerups ) );
+        for player in &mut self.players
+        {
+            if !player.is_on_floor()
+            {
+                continue;
+            }
+            if let Some( kind ) = self.spawner.collect_at( player.get_pos_x(), player.get_pos_y() )
+            {
+                player.add_boost( kind );
+            }
+        }
+    }
+
+    pub fn get_powerups( &self ) -> &[Powerup]
+    {
+        &self.spawner.powerups
+    }
+
+    //a robot touched by someone else's tile in the physics step loses a hit point, or is stunned
+    //under the knockback rules, unless its shield takes the hit. the tile breaks on the first robot it
+    //meets, goes through teammates without friendly fire and robots that were just hit or came back,
+    //and tiles meeting in the air break each other
+    fn handle_hits( &mut self, hits: &[( usize, usize )] )
+    {
+        for &( player_index, projectile_index ) in hits
+        {
+            let player = &mut self.players[player_index];
+            let projectile = &mut self.projectiles[projectile_index];
+            if player.is_dead() || player.is_invulnerable() || projectile.is_dead()
+            {
+                continue;
+            }
+            projectile.kill();
+            if player.absorb_hit()
+            {
+                continue;
+            }
+            //a heavy tile stuns longer whenever the hit knocks the robot back rather than taking a life
+            let knocked_back = self.config.knockback || player.get_hit_points() > 1;
+            if self.config.knockback
+            {
+                player.stun( KNOCKBACK_STUN_TICKS );
+            }
+            else
+            {
+                player.on_hit( &self.tile_map, &mut self.anims );
+            }
+            if knocked_back && projectile.get_kind() == TileKind::Heavy
+            {
+                player.stun( HEAVY_STUN_TICKS );
+            }
+        }
+
+        let pairs = {
+            let tiles : Vec<&Collider> = self.projectiles.iter().map(|projectile| projectile.get_collider()).collect();
+            get_overlapping_pairs( &tiles, &tiles )
+        };
+        for ( first, second ) in pairs
+        {
+            if first >= second || self.projectiles[first].is_dead() || self.projectiles[second].is_dead()
+            {
+                continue;
+            }
+            if self.projectiles[first].get_collider().touches( self.projectiles[second].get_collider() )
+            {
+                self.projectiles[first].kill();
+                self.projectiles[second].kill();
+            }
+        }
+    }
+
+    fn update_round( &mut self )
+    {
+        match self.round_over
+        {
+            None =>
+            {
+                let outcome = self.get_round_outcome();
+                if outcome != Outcome::InProgress
+                {
+                    self.round_over = Some( ( self.tick, outcome ) );
+                    //a draw scores nothing and the round is simply played again
+                    if let Outcome::Winner( id ) = outcome
+                    {
+                        self.scores[id as usize - 1] += 1;
+                    }
+                }
+            }
+            Some( ( over_tick, _ ) ) =>
+            {
+                if self.tick - over_tick >= ROUND_DELAY_TICKS && self.get_outcome() == Outcome::InProgress
+                {
+                    self.start_round();
+                }
+            }
+        }
+    }
+
+    //turns held/released directions into the same calls the key events used to make
+    fn apply_input( &mut self, index: usize, input: PlayerInput )
+    {
+        let prev = self.held[index];
+        let player = &mut self.players[index];
+        if prev.up && !input.up { player.on_dir_released( Direction::UP ); }
+        if prev.down && !input.down { player.on_dir_released( Direction::DOWN ); }
+        if prev.left && !input.left { player.on_dir_released( Direction::LEFT ); }
+        if prev.right && !input.right { player.on_dir_released( Direction::RIGHT ); }
+
+        if input.up && !prev.up { player.set_vel_y( -PLAYER_SPEED ); }
+        if input.down && !prev.down { player.set_vel_y( PLAYER_SPEED ); }
+        if input.left && !prev.left { player.set_vel_x( -PLAYER_SPEED ); }
+        if input.right && !prev.right { player.set_vel_x( PLAYER_SPEED ); }
+
+        if input.has_stick()
+        {
+            let vel_x = input.stick_x as f32 / STICK_MAX as f32 * PLAYER_SPEED;
+            let vel_y = input.stick_y as f32 / STICK_MAX as f32 * PLAYER_SPEED;
+            player.set_vel_x( vel_x );
+            player.set_vel_y( vel_y );
+        }
+        else if prev.has_stick()
+        {
+            //the stick let go, whatever is still held on the pad or keys takes over
+            player.set_vel_x( 0.0 );
+            player.set_vel_y( 0.0 );
+            if input.up { player.set_vel_y( -PLAYER_SPEED ); }
+            if input.down { player.set_vel_y( PLAYER_SPEED ); }
+            if input.left { player.set_vel_x( -PLAYER_SPEED ); }
+            if input.right { player.set_vel_x( PLAYER_SPEED ); }
+        }
+
+        if input.action
+        {
+            match self.get_shove_target( index )
+            {
+                Some( target ) => self.shove( index, target ),
+                None => self.players[index].on_action( &mut self.tile_map, &mut self.projectiles ),
+            }
+        }
+        self.held[index] = input;
+    }
+
+    //the closest robot the player at index would shove with an action
+    fn get_shove_target( &self, index: usize ) -> Option<usize>
+    {
+        let player = &self.players[index];
+        let distance = |other: &Player| {
+            let dist_x = other.get_pos_x() - player.get_pos_x();
+            let dist_y = other.get_pos_y() - player.get_pos_y();
+            dist_x * dist_x + dist_y * dist_y
+        };
+        ( 0..self.players.len() )
+            .filter(|other| player.is_in_shove_reach( &self.players[*other] ))
+            .filter(|other| self.config.can_hit( player.get_id(), self.players[*other].get_id() ))
+            .min_by(|a, b| distance( &self.players[*a] ).partial_cmp( &distance( &self.players[*b] ) ).unwrap_or( Ordering::Equal ))
+    }
+
+    fn shove( &mut self, index: usize, target: usize )
+    {
+        let ( player, other ) = if index < target
+        {
+            let ( low, high ) = self.players.split_at_mut( target );
+            ( &mut low[index], &mut high[0] )
+        }
+        else
+        {
+            let ( low, high ) = self.players.split_at_mut( index );
+            ( &mut high[0], &mut low[target] )
+        };
+        player.shove( other );
+    }
+
+    pub fn get_config( &self ) -> &MatchConfig
+    {
+        &self.config
+    }
+
+    pub fn get_round( &self ) -> u32
+    {
+        self.round
+    }
+
+    pub fn get_scores( &self ) -> &[u32]
+    {
+        &self.scores
+    }
+
+    //how the current round was decided, None while it is still being played
+    pub fn get_round_result( &self ) -> Option<Outcome>
+    {
+        self.round_over.map(|( _, outcome )| outcome)
+    }
+
+    //how long the current round took to decide, or has been going so far
+    pub fn get_round_ticks( &self ) -> u64
+    {
+        self.round_over.map_or( self.tick, |( tick, _ )| tick ) - self.round_start_tick
+    }
+
+    //the first to win enough rounds takes the match
+    pub fn get_outcome( &self ) -> Outcome
+    {
+        match self.scores.iter().position(|score| *score >= self.config.rounds_to_win)
+        {
+            Some( index ) => Outcome::Winner( index as u32 + 1 ),
+            None => Outcome::InProgress,
+        }
+    }
+
+    //up to the game mode, in the classic one the last robot or team standing wins
+    pub fn get_round_outcome( &self ) -> Outcome
+    {
+        self.mode.get_round_outcome( &self.config, &self.players )
+    }
+
+    pub fn get_mode( &self ) -> &dyn GameMode
+    {
+        &*self.mode
+    }
+
+    //fingerprint of the simulated state, two games fed the same inputs must always agree on it
+    pub fn checksum( &self ) -> u64
+    {
+        let mut hash = Fnv::new();
+        hash.write_u64( self.tick );
+        hash.write_u32( self.round );
+        for score in &self.scores
+        {
+            hash.write_u32( *score );
+        }
+        for player in &self.players
+        {
+            hash.write_u32( player.get_pos_x().to_bits() );
+            hash.write_u32( player.get_pos_y().to_bits() );
+            hash.write_u32( player.get_scale().to_bits() );
+            hash.write_u32( player.get_aim_x().to_bits() );
+            hash.write_u32( player.get_aim_y().to_bits() );
+            hash.write_u32( player.get_move_vel_x().to_bits() );
+            hash.write_u32( player.get_move_vel_y().to_bits() );
+            hash.write_u32( player.is_stunned() as u32 );
+            hash.write_u32( player.get_shove_cooldown() );
+            hash.write_u32( player.get_boosts().get_bits() );
+            hash.write_u32( player.is_dead() as u32 );
+            hash.write_u32( player.get_hit_points() );
+            hash.write_u32( player.get_lives() );
+            hash.write_u32( player.get_invulnerable_ticks() );
+            hash.write_u32( player.get_held_tile().map_or( 0, |tile| tile.image_id as u32 ) );
+            hash.write_u32( player.get_held_tile().map_or( 0, |tile| tile.kind.get_index() as u32 ) );
+        }
+        for projectile in &self.projectiles
+        {
+            hash.write_u32( projectile.get_pos_x().to_bits() );
+            hash.write_u32( projectile.get_pos_y().to_bits() );
+            hash.write_u32( projectile.get_vel_x().to_bits() );
+            hash.write_u32( projectile.get_vel_y().to_bits() );
+        }
+        for powerup in self.get_powerups()
+        {
+            hash.write_u32( powerup.kind.get_index() as u32 );
+            hash.write_u32( ( powerup.index_y * self.tile_map.get_num_tiles_x() + powerup.index_x ) as u32 );
+        }
+        self.mode.hash( &mut hash );
+        self.rng.hash( &mut hash );
+        for row in &self.tile_map.map
+        {
+            for tile in row
+            {
+                hash.write_u32( ( *tile.get_state() == TileState::FULL ) as u32 );
+                hash.write_u32( tile.kind.get_index() as u32 );
+                hash.write_u32( tile.get_crumble_ticks() );
+            }
+        }
+        hash.finish()
+    }
+}
+
+//fnv-1a, spelled out so the checksum never changes between compiler versions
+pub struct Fnv
+{
+    state: u64,
+}
+
+impl Fnv
+{
+    pub fn new() -> Fnv
+    {
+        Fnv { state: 0xcbf29ce484222325 }
+    }
+
+    fn write_u8( &mut self, byte: u8 )
+    {
+        self.state ^= byte as u64;
+        self.state = self.state.wrapping_mul( 0x100000001b3 );
+    }
+
+    pub fn write_u32( &mut self, value: u32 )
+    {
+        for shift in 0..4
+        {
+            self.write_u8( ( value >> ( shift * 8 ) ) as u8 );
+        }
+    }
+
+    pub fn write_u64( &mut self, value: u64 )
+    {
+        self.write_u32( value as u32 );
+        self.write_u32( ( value >> 32 ) as u32 );
+    }
+
+    pub fn finish( &self ) -> u64
+    {
+        self.state
+    }
+}
@@ -0,0 +1,565 @@
+use game::*;
+use player::*;
+use tile::*;
+use anim::*;
+use rng::*;
+use projectile::*;
+use campaign::*;
+
+//king of the tile: total time on the hill needed to take the round
+const KING_HOLD_TICKS : u32 = 15 * TICKS_PER_SECOND;
+//tile hoard: tiles brought home needed to take the round
+const HOARD_TILES_TO_WIN : u32 = 5;
+//tiles picked up this close to a base don't count, or robots would just empty the floor around it
+const HOARD_MIN_CELLS : usize = 3;
+//survival: the sky starts falling after a short grace period and keeps it up
+const SURVIVAL_FIRST_WAVE_TICKS : u32 = 3 * TICKS_PER_SECOND;
+const SURVIVAL_WAVE_TICKS : u32 = 5 * TICKS_PER_SECOND;
+//how long the shadow of a falling tile shows where it comes down
+const SKY_WARNING_TICKS : u32 = TICKS_PER_SECOND;
+const MAX_WAVE_SIZE : usize = 8;
+//under the knockback rules a falling tile throws robots off the cell instead
+const SKY_KNOCKBACK_SPEED : f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameModeKind
+{
+    //the last robot or team standing takes the round
+    LastStanding,
+    //standing alone on a marked cell scores
+    KingOfTheTile,
+    //tiles brought back to the base score
+    TileHoard,
+    //tiles rain down on the crumbling floor
+    Survival,
+    //one of the campaign stages, only ever played from the campaign
+    Challenge( usize ),
+}
+
+pub const GAME_MODE_KINDS : [GameModeKind; 4] = [
+    GameModeKind::LastStanding,
+    GameModeKind::KingOfTheTile,
+    GameModeKind::TileHoard,
+    GameModeKind::Survival,
+];
+
+impl GameModeKind
+{
+    pub fn get_name( &self ) -> &'static str
+    {
+        match *self
+        {
+            GameModeKind::LastStanding => "classic",
+            GameModeKind::KingOfTheTile => "king",
+            GameModeKind::TileHoard => "hoard",
+            GameModeKind::Survival => "survival",
+            GameModeKind::Challenge( _ ) => "challenge",
+        }
+    }
+
+    pub fn from_name( name: &str ) -> Option<GameModeKind>
+    {
+        GAME_MODE_KINDS.iter().cloned().find(|kind| kind.get_name() == name)
+    }
+
+    //position in GAME_MODE_KINDS, used in replays and packets. the campaign stages follow on
+    pub fn get_index( &self ) -> usize
+    {
+        match *self
+        {
+            GameModeKind::Challenge( stage ) => GAME_MODE_KINDS.len() + stage,
+            _ => GAME_MODE_KINDS.iter().position(|kind| kind == self).unwrap_or( 0 ),
+        }
+    }
+
+    pub fn from_index( index: usize ) -> Option<GameModeKind>
+    {
+        match GAME_MODE_KINDS.get( index )
+        {
+            Some( kind ) => Some( *kind ),
+            None if index - GAME_MODE_KINDS.len() < STAGES.len() => Some( GameModeKind::Challenge( index - GAME_MODE_KINDS.len() ) ),
+            None => None,
+        }
+    }
+
+    pub fn create( &self ) -> Box<dyn GameMode>
+    {
+        match *self
+        {
+            GameModeKind::LastStanding => Box::new( LastStanding ),
+            GameModeKind::KingOfTheTile => Box::new( KingOfTheTile::default() ),
+            GameModeKind::TileHoard => Box::new( TileHoard::default() ),
+            GameModeKind::Survival => Box::new( Survival::default() ),
+            GameModeKind::Challenge( stage ) => Box::new( Challenge::new( stage ) ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MarkerKind
+{
+    Hill,
+    Base,
+    //a tile coming down in this many ticks
+    Warning( u32 ),
+    //the campaign's dummies, the cell to reach and the turrets
+    Target,
+    Flag,
+    Turret,
+}
+
+//a cell a mode wants highlighted, side is who it belongs to or who holds it right now
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Marker
+{
+    pub kind: MarkerKind,
+    pub index_x: usize,
+    pub index_y: usize,
+    pub side: Option<u32>,
+}
+
+//the rules that decide a round. a mode is part of the game state: it is cloned along with it for
+//rollback, must only change on ticks, and everything it keeps goes into the checksum
+pub trait GameMode
+{
+    //the arena and the robots have just been put back for a new round
+    fn start_round( &mut self, config: &MatchConfig, players: &[Player], tile_map: &TileMap );
+    //called every tick once the robots have moved and the hits are handled, rng is the mode's own stream
+    fn update( &mut self, config: &MatchConfig, players: &mut [Player], tile_map: &mut TileMap, projectiles: &mut Vec<Projectile>, anims: &mut Vec<Anim>, rng: &mut Rng );
+    fn get_round_outcome( &self, config: &MatchConfig, players: &[Player] ) -> Outcome;
+    //the floor crumbles away whatever the match settings say
+    fn always_shrinks( &self ) -> bool
+    {
+        false
+    }
+    //a line for the scoreboard, empty when there is nothing to add
+    fn get_hud_text( &self, config: &MatchConfig ) -> String;
+    fn get_markers( &self ) -> Vec<Marker>;
+    fn hash( &self, hash: &mut Fnv );
+    fn box_clone( &self ) -> Box<dyn GameMode>;
+}
+
+impl Clone for Box<dyn GameMode>
+{
+    fn clone( &self ) -> Box<dyn GameMode>
+    {
+        self.box_clone()
+    }
+}
+
+//last robot or team standing wins, if the last ones go down together it is a draw
+pub fn get_last_standing( config: &MatchConfig, players: &[Player] ) -> Outcome
+{
+    let mut alive = players.iter()
+        .filter(|player| !player.is_dead())
+        .map(|player| config.get_team( player.get_id() ));
+    let first = match alive.next()
+    {
+        Some( team ) => team,
+        None => return Outcome::Draw,
+    };
+    if alive.all(|team| team == first)
+    {
+        Outcome::Winner( first )
+    }
+    else
+    {
+        Outcome::InProgress
+    }
+}
+
+//the first side to reach the target takes the round, otherwise it is played out to the last robot
+fn get_first_to( config: &MatchConfig, players: &[Player], scores: &[u32], target: u32 ) -> Outcome
+{
+    match scores.iter().position(|score| *score >= target)
+    {
+        Some( index ) => Outcome::Winner( index as u32 + 1 ),
+        None => get_last_standing( config, players ),
+    }
+}
+
+fn get_cell( pos_x: f32, pos_y: f32 ) -> ( usize, usize )
+{
+    ( pos_x.max( 0.0 ) as usize / TILE_SPACE, pos_y.max( 0.0 ) as usize / TILE_SPACE )
+}
+
+pub fn get_cell_center( index_x: usize, index_y: usize ) -> ( f32, f32 )
+{
+    ( ( index_x * TILE_SPACE + TILE_SIZE / 2 ) as f32, ( index_y * TILE_SPACE + TILE_SIZE / 2 ) as f32 )
+}
+
+fn format_scores( config: &MatchConfig, scores: &[u32], scale: u32 ) -> String
+{
+    let scores : Vec<String> = scores.iter()
+        .enumerate()
+        .map(|( index, score )| format!( "{}{} {}", config.get_side_prefix(), index + 1, score / scale ))
+        .collect();
+    scores.join( "  " )
+}
+
+#[derive(Clone)]
+pub struct LastStanding;
+
+impl GameMode for LastStanding
+{
+    fn start_round( &mut self, _config: &MatchConfig, _players: &[Player], _tile_map: &TileMap ) {}
+
+    fn update( &mut self, _config: &MatchConfig, _players: &mut [Player], _tile_map: &mut TileMap, _projectiles: &mut Vec<Projectile>, _anims: &mut Vec<Anim>, _rng: &mut Rng ) {}
+
+    fn get_round_outcome( &self, config: &MatchConfig, players: &[Player] ) -> Outcome
+    {
+        get_last_standing( config, players )
+    }
+
+    fn get_hud_text( &self, _config: &MatchConfig ) -> String
+    {
+        String::new()
+    }
+
+    fn get_markers( &self ) -> Vec<Marker>
+    {
+        Vec::new()
+    }
+
+    fn hash( &self, _hash: &mut Fnv ) {}
+
+    fn box_clone( &self ) -> Box<dyn GameMode>
+    {
+        Box::new( self.clone() )
+    }
+}
+
+//one cell near the middle is the hill, a side scores every tick it has a robot on it and nobody
+//else does. when the hill's tile goes, the hill moves to the closest floor left
+#[derive(Clone, Default)]
+pub struct KingOfTheTile
+{
+    hill: Option<( usize, usize )>,
+    //ticks on the hill, indexed by side - 1
+    held: Vec<u32>,
+    holder: Option<u32>,
+}
+
+impl KingOfTheTile
+{
+    fn move_hill( &mut self, tile_map: &TileMap, pos_x: f32, pos_y: f32 )
+    {
+        self.hill = tile_map.find_safe_floor_near( pos_x, pos_y ).map(|( pos_x, pos_y )| get_cell( pos_x, pos_y ));
+    }
+}
+
+impl GameMode for KingOfTheTile
+{
+    fn start_round( &mut self, config: &MatchConfig, _players: &[Player], tile_map: &TileMap )
+    {
+        self.held = vec![0; config.get_num_sides()];
+        self.holder = None;
+        let center_x = ( tile_map.get_num_tiles_x() * TILE_SPACE ) as f32 / 2.0;
+        let center_y = ( tile_map.get_num_tiles_y() * TILE_SPACE ) as f32 / 2.0;
+        self.move_hill( tile_map, center_x, center_y );
+    }
+
+    fn update( &mut self, config: &MatchConfig, players: &mut [Player], tile_map: &mut TileMap, _projectiles: &mut Vec<Projectile>, _anims: &mut Vec<Anim>, _rng: &mut Rng )
+    {
+        self.holder = None;
+        let ( index_x, index_y ) = match self.hill
+        {
+            Some( hill ) => hill,
+            None => return,
+        };
+        let ( center_x, center_y ) = get_cell_center( index_x, index_y );
+        if !tile_map.is_safe_at( center_x, center_y )
+        {
+            self.move_hill( tile_map, center_x, center_y );
+            return;
+        }
+
+        let mut sides = players.iter()
+            .filter(|player| player.is_on_floor() && get_cell( player.get_pos_x(), player.get_pos_y() ) == ( index_x, index_y ))
+            .map(|player| config.get_team( player.get_id() ));
+        let side = match sides.next()
+        {
+            Some( side ) => side,
+            None => return,
+        };
+        if sides.all(|other| other == side)
+        {
+            self.holder = Some( side );
+            self.held[side as usize - 1] += 1;
+        }
+    }
+
+    fn get_round_outcome( &self, config: &MatchConfig, players: &[Player] ) -> Outcome
+    {
+        get_first_to( config, players, &self.held, KING_HOLD_TICKS )
+    }
+
+    fn get_hud_text( &self, config: &MatchConfig ) -> String
+    {
+        format!( "Hold the hill {}s: {}", KING_HOLD_TICKS / TICKS_PER_SECOND, format_scores( config, &self.held, TICKS_PER_SECOND ) )
+    }
+
+    fn get_markers( &self ) -> Vec<Marker>
+    {
+        self.hill.iter()
+            .map(|&( index_x, index_y )| Marker { kind: MarkerKind::Hill, index_x, index_y, side: self.holder })
+            .collect()
+    }
+
+    fn hash( &self, hash: &mut Fnv )
+    {
+        let ( index_x, index_y ) = self.hill.unwrap_or( ( usize::max_value(), usize::max_value() ) );
+        hash.write_u32( index_x as u32 );
+        hash.write_u32( index_y as u32 );
+        for held in &self.held
+        {
+            hash.write_u32( *held );
+        }
+    }
+
+    fn box_clone( &self ) -> Box<dyn GameMode>
+    {
+        Box::new( self.clone() )
+    }
+}
+
+//every side has a base where its first robot starts. a robot standing on or next to its base with
+//a tile picked up far enough away puts it in the hoard
+#[derive(Clone, Default)]
+pub struct TileHoard
+{
+    //indexed by side - 1
+    bases: Vec<( usize, usize )>,
+    hoarded: Vec<u32>,
+}
+
+fn get_cell_distance( a: ( usize, usize ), b: ( usize, usize ) ) -> usize
+{
+    let dist_x = if a.0 > b.0 { a.0 - b.0 } else { b.0 - a.0 };
+    let dist_y = if a.1 > b.1 { a.1 - b.1 } else { b.1 - a.1 };
+    dist_x.max( dist_y )
+}
+
+impl GameMode for TileHoard
+{
+    fn start_round( &mut self, config: &MatchConfig, players: &[Player], _tile_map: &TileMap )
+    {
+        let num_sides = config.get_num_sides();
+        self.hoarded = vec![0; num_sides];
+        self.bases = ( 1..num_sides as u32 + 1 )
+            .filter_map(|side| players.iter().find(|player| config.get_team( player.get_id() ) == side))
+            .map(|player| get_cell( player.get_pos_x(), player.get_pos_y() ))
+            .collect();
+    }
+
+    fn update( &mut self, config: &MatchConfig, players: &mut [Player], _tile_map: &mut TileMap, _projectiles: &mut Vec<Projectile>, anims: &mut Vec<Anim>, _rng: &mut Rng )
+    {
+        for player in players.iter_mut()
+        {
+            if !player.is_on_floor()
+            {
+                continue;
+            }
+            let side = config.get_team( player.get_id() ) as usize;
+            let base = match self.bases.get( side - 1 )
+            {
+                Some( base ) => *base,
+                None => continue,
+            };
+            let origin = match player.get_held_tile()
+            {
+                Some( tile ) => get_cell( tile.get_pos_x() as f32, tile.get_pos_y() as f32 ),
+                None => continue,
+            };
+            if get_cell_distance( get_cell( player.get_pos_x(), player.get_pos_y() ), base ) <= 1
+                && get_cell_distance( origin, base ) >= HOARD_MIN_CELLS
+            {
+                player.take_held_tile();
+                self.hoarded[side - 1] += 1;
+                let ( center_x, center_y ) = get_cell_center( base.0, base.1 );
+                let prefix = "/hit_animation/hit_animation_".to_owned();
+                anims.push( Anim::new_owned( center_x - 16.0, center_y - 16.0, prefix, 3, 4, player.get_id() ) );
+            }
+        }
+    }
+
+    fn get_round_outcome( &self, config: &MatchConfig, players: &[Player] ) -> Outcome
+    {
+        get_first_to( config, players, &self.hoarded, HOARD_TILES_TO_WIN )
+    }
+
+    fn get_hud_text( &self, config: &MatchConfig ) -> String
+    {
+        format!( "Bring {} tiles home: {}", HOARD_TILES_TO_WIN, format_scores( config, &self.hoarded, 1 ) )
+    }
+
+    fn get_markers( &self ) -> Vec<Marker>
+    {
+        self.bases.iter()
+            .enumerate()
+            .map(|( index, &( index_x, index_y ) )| Marker { kind: MarkerKind::Base, index_x, index_y, side: Some( index as u32 + 1 ) })
+            .collect()
+    }
+
+    fn hash( &self, hash: &mut Fnv )
+    {
+        for hoarded in &self.hoarded
+        {
+            hash.write_u32( *hoarded );
+        }
+    }
+
+    fn box_clone( &self ) -> Box<dyn GameMode>
+    {
+        Box::new( self.clone() )
+    }
+}
+
+//a tile on its way down from the sky onto a cell
+#[derive(Clone)]
+struct SkyTile
+{
+    index_x: usize,
+    index_y: usize,
+    ticks_left: u32,
+}
+
+//the floor crumbles and waves of tiles fall on it, each wave bigger than the last. the tiles come
+//down on random floor cells drawn from the match's stream, so replays see the same waves
+#[derive(Clone)]
+pub struct Survival
+{
+    wave: u32,
+    ticks_to_wave: u32,
+    sky: Vec<SkyTile>,
+}
+
+impl Default for Survival
+{
+    fn default() -> Survival
+    {
+        Survival
+        {
+            wave: 0,
+            ticks_to_wave: SURVIVAL_FIRST_WAVE_TICKS,
+            sky: Vec::new(),
+        }
+    }
+}
+
+impl Survival
+{
+    //robots too close to the middle of the cell are hit like by a thrown tile, with nobody to blame
+    fn land( &self, sky_tile: &SkyTile, config: &MatchConfig, players: &mut [Player], tile_map: &TileMap, anims: &mut Vec<Anim> )
+    {
+        let ( center_x, center_y ) = get_cell_center( sky_tile.index_x, sky_tile.index_y );
+        let reach = ( TILE_SIZE as f32 + ROBOT_RADIUS ) / 2.0;
+        for player in players.iter_mut()
+        {
+            let dist_x = player.get_pos_x() - center_x;
+            let dist_y = player.get_pos_y() - center_y;
+            if !player.is_on_floor() || player.is_invulnerable() || dist_x.abs() > reach || dist_y.abs() > reach
+            {
+                continue;
+            }
+            if player.absorb_hit()
+            {
+                continue;
+            }
+            if config.knockback
+            {
+                let dist = ( dist_x * dist_x + dist_y * dist_y ).sqrt().max( 1.0 );
+                player.knock_back( dist_x / dist * SKY_KNOCKBACK_SPEED, dist_y / dist * SKY_KNOCKBACK_SPEED );
+            }
+            else
+            {
+                player.on_hit( tile_map, anims );
+            }
+        }
+        let prefix = "/collision/collision_animation_".to_owned();
+        anims.push( Anim::new( center_x - 32.0, center_y - 32.0, prefix, 4, 10 ) );
+    }
+
+    fn start_wave( &mut self, tile_map: &TileMap, rng: &mut Rng )
+    {
+        self.wave += 1;
+        let mut cells = tile_map.get_safe_cells();
+        for _ in 0..( self.wave as usize + 1 ).min( MAX_WAVE_SIZE )
+        {
+            if cells.is_empty()
+            {
+                break;
+            }
+            let index = rng.next_below( cells.len() );
+            let ( index_x, index_y ) = cells.swap_remove( index );
+            self.sky.push( SkyTile { index_x, index_y, ticks_left: SKY_WARNING_TICKS } );
+        }
+    }
+}
+
+impl GameMode for Survival
+{
+    fn start_round( &mut self, _config: &MatchConfig, _players: &[Player], _tile_map: &TileMap )
+    {
+        *self = Survival::default();
+    }
+
+    fn update( &mut self, config: &MatchConfig, players: &mut [Player], tile_map: &mut TileMap, _projectiles: &mut Vec<Projectile>, anims: &mut Vec<Anim>, rng: &mut Rng )
+    {
+        for sky_tile in &mut self.sky
+        {
+            sky_tile.ticks_left = sky_tile.ticks_left.saturating_sub( 1 );
+        }
+        for sky_tile in self.sky.iter().filter(|sky_tile| sky_tile.ticks_left == 0)
+        {
+            self.land( sky_tile, config, players, tile_map, anims );
+        }
+        self.sky.retain(|sky_tile| sky_tile.ticks_left > 0);
+
+        self.ticks_to_wave = self.ticks_to_wave.saturating_sub( 1 );
+        if self.ticks_to_wave == 0
+        {
+            self.ticks_to_wave = SURVIVAL_WAVE_TICKS;
+            self.start_wave( tile_map, rng );
+        }
+    }
+
+    fn get_round_outcome( &self, config: &MatchConfig, players: &[Player] ) -> Outcome
+    {
+        get_last_standing( config, players )
+    }
+
+    fn always_shrinks( &self ) -> bool
+    {
+        true
+    }
+
+    fn get_hud_text( &self, _config: &MatchConfig ) -> String
+    {
+        format!( "Wave {}, the next one in {}s", self.wave, ( self.ticks_to_wave + TICKS_PER_SECOND - 1 ) / TICKS_PER_SECOND )
+    }
+
+    fn get_markers( &self ) -> Vec<Marker>
+    {
+        self.sky.iter()
+            .map(|sky_tile| Marker { kind: MarkerKind::Warning( sky_tile.ticks_left ), index_x: sky_tile.index_x, index_y: sky_tile.index_y, side: None })
+            .collect()
+    }
+
+    fn hash( &self, hash: &mut Fnv )
+    {
+        hash.write_u32( self.wave );
+        hash.write_u32( self.ticks_to_wave );
+        for sky_tile in &self.sky
+        {
+            hash.write_u32( sky_tile.index_x as u32 );
+            hash.write_u32( sky_tile.index_y as u32 );
+            hash.write_u32( sky_tile.ticks_left );
+        }
+    }
+
+    fn box_clone( &self ) -> Box<dyn GameMode>
+    {
+        Box::new( self.clone() )
+    }
+}
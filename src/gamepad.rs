@@ -0,0 +1,153 @@
+use ggez::Context;
+use ggez::event::Axis;
+use ggez::input::GameController;
+
+use input::*;
+
+//how far a stick has to be pushed, out of 32767, before it moves the robot
+const DEAD_ZONE : f32 = 8000.0;
+const AXIS_MAX : f32 = 32767.0;
+
+struct Pad
+{
+    controller: GameController,
+    //None while every free player slot already has a pad
+    slot: Option<usize>,
+    axis_x: i16,
+    axis_y: i16,
+}
+
+pub enum Hotplug
+{
+    Connected( usize, String ),
+    Disconnected( usize ),
+}
+
+//turns raw stick axes into a PlayerInput stick, zero inside the dead zone and rescaled so it
+//grows smoothly from its edge up to full tilt
+fn get_stick( axis_x: i16, axis_y: i16 ) -> ( i8, i8 )
+{
+    let ( x, y ) = ( axis_x as f32, axis_y as f32 );
+    let length = ( x * x + y * y ).sqrt();
+    if length < DEAD_ZONE
+    {
+        return ( 0, 0 );
+    }
+    let tilt = ( ( length - DEAD_ZONE ) / ( AXIS_MAX - DEAD_ZONE ) ).min( 1.0 );
+    let scale = tilt / length * STICK_MAX as f32;
+    ( ( x * scale ).round() as i8, ( y * scale ).round() as i8 )
+}
+
+//the connected gamepads and which player slot each one drives. ggez only opens the pads that are
+//there at startup, so pads plugged in later are picked up here
+pub struct Gamepads
+{
+    pads: Vec<Pad>,
+    num_devices: u32,
+}
+
+impl Gamepads
+{
+    pub fn new() -> Gamepads
+    {
+        Gamepads
+        {
+            pads: Vec::new(),
+            num_devices: 0,
+        }
+    }
+
+    //called every frame with the slots pads may drive, in the order they are handed out
+    pub fn update( &mut self, ctx: &Context, slots: &[usize] ) -> Vec<Hotplug>
+    {
+        let mut changes = Vec::new();
+        let num_pads = self.pads.len();
+        self.pads.retain(|pad| {
+            if pad.controller.attached()
+            {
+                return true;
+            }
+            if let Some( slot ) = pad.slot
+            {
+                changes.push( Hotplug::Disconnected( slot ) );
+            }
+            false
+        });
+
+        let subsystem = match ctx.sdl_context.game_controller()
+        {
+            Ok( subsystem ) => subsystem,
+            Err( _ ) => return changes,
+        };
+        let num_devices = subsystem.num_joysticks().unwrap_or( 0 );
+        if num_devices != self.num_devices || self.pads.len() != num_pads
+        {
+            self.num_devices = num_devices;
+            for index in 0..num_devices
+            {
+                if !subsystem.is_game_controller( index )
+                {
+                    continue;
+                }
+                if let Ok( controller ) = subsystem.open( index )
+                {
+                    if !self.pads.iter().any(|pad| pad.controller.instance_id() == controller.instance_id())
+                    {
+                        self.pads.push( Pad { controller, slot: None, axis_x: 0, axis_y: 0 } );
+                    }
+                }
+            }
+        }
+
+        //slots can go away, e.g. when a playtest has fewer players
+        for pad in &mut self.pads
+        {
+            if pad.slot.map_or( false, |slot| !slots.contains( &slot ) )
+            {
+                changes.push( Hotplug::Disconnected( pad.slot.unwrap() ) );
+                pad.slot = None;
+            }
+        }
+        for index in 0..self.pads.len()
+        {
+            if self.pads[index].slot.is_some()
+            {
+                continue;
+            }
+            let free = slots.iter().cloned().find(|slot| !self.has_pad( *slot ));
+            if let Some( slot ) = free
+            {
+                let pad = &mut self.pads[index];
+                pad.slot = Some( slot );
+                changes.push( Hotplug::Connected( slot, pad.controller.name() ) );
+            }
+        }
+        changes
+    }
+
+    pub fn has_pad( &self, slot: usize ) -> bool
+    {
+        self.pads.iter().any(|pad| pad.slot == Some( slot ))
+    }
+
+    //the player slot a pad drives, if it has one
+    pub fn get_slot( &self, instance_id: i32 ) -> Option<usize>
+    {
+        self.pads.iter()
+            .find(|pad| pad.controller.instance_id() == instance_id)
+            .and_then(|pad| pad.slot)
+    }
+
+    //the slot and its new stick after the left stick moved
+    pub fn on_axis( &mut self, instance_id: i32, axis: Axis, value: i16 ) -> Option<( usize, ( i8, i8 ) )>
+    {
+        let pad = self.pads.iter_mut().find(|pad| pad.controller.instance_id() == instance_id)?;
+        match axis
+        {
+            Axis::LeftX => { pad.axis_x = value }
+            Axis::LeftY => { pad.axis_y = value }
+            _ => return None,
+        }
+        Some( ( pad.slot?, get_stick( pad.axis_x, pad.axis_y ) ) )
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::VecDeque;
+
+use arena::*;
+use player::*;
+use rng::*;
+use tile::*;
+use tilesheet::*;
+
+//"--arena random" lays out a fresh arena, "--arena random-<seed>" the same one again
+pub static GENERATED_ARENA : &'static str = "random";
+//fresh seeds are kept short so they are easy to read out and share
+pub const MAX_FRESH_SEED : u64 = 1000000;
+
+//empty cells around the floor, so there is always somewhere to fall
+const BORDER : usize = 2;
+//the floor is made of four mirrored quarters, each this many cells
+const MIN_QUARTER_X : usize = 4;
+const MAX_QUARTER_X : usize = 6;
+const MIN_QUARTER_Y : usize = 5;
+const MAX_QUARTER_Y : usize = 8;
+const MAX_HOLES : usize = 3;
+const MAX_CLUSTERS : usize = 2;
+//a layout that loses more of its floor than this to gaps, holes and cut off islands is thrown away
+const MIN_FLOOR_PERCENT : usize = 60;
+//after this many unfair layouts the plain floor is used, which is always fair
+const MAX_ATTEMPTS : usize = 100;
+
+const SPECIAL_KINDS : [TileKind; 5] = [
+    TileKind::Cracked,
+    TileKind::Ice,
+    TileKind::Heavy,
+    TileKind::Explosive,
+    TileKind::Conveyor( Direction::UP ),
+];
+
+const DIRECTIONS : [Direction; 4] = [Direction::UP, Direction::RIGHT, Direction::DOWN, Direction::LEFT];
+
+pub fn get_generated_name( seed: u64 ) -> String
+{
+    format!( "{}-{}", GENERATED_ARENA, seed )
+}
+
+//the seed of a generated arena's name, None for any other name
+pub fn parse_generated_name( name: &str ) -> Option<u64>
+{
+    let prefix = format!( "{}-", GENERATED_ARENA );
+    if !name.starts_with( &prefix )
+    {
+        return None;
+    }
+    name[prefix.len()..].parse().ok()
+}
+
+//the top left quarter of the floor, the others are its mirror images. cell 0 0 is the outer corner
+//and the last cell is next to the middle of the arena
+struct Quarter
+{
+    num_x: usize,
+    num_y: usize,
+    //None where there is a hole
+    cells: Vec<Option<TileKind>>,
+    spawn: ( usize, usize ),
+}
+
+impl Quarter
+{
+    fn new( num_x: usize, num_y: usize, spawn: ( usize, usize ) ) -> Quarter
+    {
+        Quarter
+        {
+            num_x,
+            num_y,
+            cells: vec![Some( TileKind::Normal ); num_x * num_y],
+            spawn,
+        }
+    }
+
+    fn set( &mut self, x_index: usize, y_index: usize, cell: Option<TileKind> )
+    {
+        self.cells[y_index * self.num_x + x_index] = cell;
+    }
+
+    fn get( &self, x_index: usize, y_index: usize ) -> Option<TileKind>
+    {
+        self.cells[y_index * self.num_x + x_index]
+    }
+
+    //the spawn and the ring around it are always plain floor, so no one starts next to a hole
+    fn is_protected( &self, x_index: usize, y_index: usize ) -> bool
+    {
+        let ( spawn_x, spawn_y ) = self.spawn;
+        ( x_index as i32 - spawn_x as i32 ).abs() <= 1 && ( y_index as i32 - spawn_y as i32 ).abs() <= 1
+    }
+
+    fn carve( &mut self, x_index: usize, y_index: usize )
+    {
+        if !self.is_protected( x_index, y_index )
+        {
+            self.set( x_index, y_index, None );
+        }
+    }
+
+    //a gap across the quarter, along a row or a column, with a single cell bridge left over it
+    fn carve_gap( &mut self, rng: &mut Rng, row: Option<usize>, column: Option<usize> )
+    {
+        let ( cells, bridge ) = match ( row, column )
+        {
+            ( Some( y_index ), _ ) =>
+            {
+                let bridge = rng.next_below( self.num_x );
+                ( ( 0..self.num_x ).map(|x_index| ( x_index, y_index )).collect::<Vec<_>>(), bridge )
+            }
+            ( None, Some( x_index ) ) =>
+            {
+                let bridge = rng.next_below( self.num_y );
+                ( ( 0..self.num_y ).map(|y_index| ( x_index, y_index )).collect::<Vec<_>>(), bridge )
+            }
+            ( None, None ) => return,
+        };
+        for ( index, &( x_index, y_index ) ) in cells.iter().enumerate()
+        {
+            if index != bridge
+            {
+                self.carve( x_index, y_index );
+            }
+        }
+    }
+
+    //a few tiles of the same special kind around one cell, conveyors all run the same way
+    fn add_cluster( &mut self, rng: &mut Rng )
+    {
+        let mut kind = SPECIAL_KINDS[rng.next_below( SPECIAL_KINDS.len() )];
+        if let TileKind::Conveyor( _ ) = kind
+        {
+            kind = TileKind::Conveyor( DIRECTIONS[rng.next_below( DIRECTIONS.len() )] );
+        }
+        let center_x = rng.next_below( self.num_x ) as i32;
+        let center_y = rng.next_below( self.num_y ) as i32;
+        for &( dx, dy ) in &[( 0, 0 ), ( -1, 0 ), ( 1, 0 ), ( 0, -1 ), ( 0, 1 )]
+        {
+            let ( x_index, y_index ) = ( center_x + dx, center_y + dy );
+            if x_index < 0 || y_index < 0 || x_index >= self.num_x as i32 || y_index >= self.num_y as i32
+            {
+                continue;
+            }
+            let ( x_index, y_index ) = ( x_index as usize, y_index as usize );
+            if !self.is_protected( x_index, y_index ) && self.get( x_index, y_index ).is_some()
+            {
+                self.set( x_index, y_index, Some( kind ) );
+            }
+        }
+    }
+}
+
+fn lay_out_quarter( rng: &mut Rng, num_x: usize, num_y: usize ) -> Quarter
+{
+    //robots start in the outer half of their quarter, clear of the edge
+    let spawn_x = 1 + rng.next_below( num_x - 2 );
+    let spawn_y = 1 + rng.next_below( ( num_y / 2 ).max( 2 ) - 1 );
+    let mut quarter = Quarter::new( num_x, num_y, ( spawn_x, spawn_y ) );
+
+    //gaps along the middle of the arena cut it into islands joined by bridges
+    if rng.next_below( 2 ) == 0
+    {
+        quarter.carve_gap( rng, None, Some( num_x - 1 ) );
+    }
+    if rng.next_below( 2 ) == 0
+    {
+        quarter.carve_gap( rng, Some( num_y - 1 ), None );
+    }
+    //and one between the spawn and the middle puts the robot on an island of its own
+    if spawn_y + 2 < num_y - 1 && rng.next_below( 3 ) == 0
+    {
+        let row = spawn_y + 2 + rng.next_below( num_y - 1 - ( spawn_y + 2 ) );
+        quarter.carve_gap( rng, Some( row ), None );
+    }
+    for _ in 0..rng.next_below( MAX_HOLES + 1 )
+    {
+        let ( x_index, y_index ) = ( rng.next_below( num_x ), rng.next_below( num_y ) );
+        quarter.carve( x_index, y_index );
+    }
+    for _ in 0..rng.next_below( MAX_CLUSTERS + 1 )
+    {
+        quarter.add_cluster( rng );
+    }
+    quarter
+}
+
+//conveyors in a mirrored quarter run the mirrored way
+fn mirror_kind( kind: TileKind, flip_x: bool, flip_y: bool ) -> TileKind
+{
+    match kind
+    {
+        TileKind::Conveyor( Direction::LEFT ) if flip_x => TileKind::Conveyor( Direction::RIGHT ),
+        TileKind::Conveyor( Direction::RIGHT ) if flip_x => TileKind::Conveyor( Direction::LEFT ),
+        TileKind::Conveyor( Direction::UP ) if flip_y => TileKind::Conveyor( Direction::DOWN ),
+        TileKind::Conveyor( Direction::DOWN ) if flip_y => TileKind::Conveyor( Direction::UP ),
+        _ => kind,
+    }
+}
+
+//the quarter mirrored into all four corners. the spawns go first and second in opposite corners so
+//two player matches face off, and teams of odd and even players get the top and the bottom half
+fn build_arena( name: &str, quarter: &Quarter ) -> Arena
+{
+    let num_tiles_x = ( quarter.num_x + BORDER ) * 2;
+    let num_tiles_y = ( quarter.num_y + BORDER ) * 2;
+    let mut arena = Arena::new_empty( name, num_tiles_x, num_tiles_y );
+    let sheetmap = SheetMap::new();
+    let flips = [( false, false ), ( true, true ), ( true, false ), ( false, true )];
+    for &( flip_x, flip_y ) in &flips
+    {
+        let get_x = |x_index: usize| if flip_x { num_tiles_x - 1 - BORDER - x_index } else { BORDER + x_index };
+        let get_y = |y_index: usize| if flip_y { num_tiles_y - 1 - BORDER - y_index } else { BORDER + y_index };
+        for y_index in 0..quarter.num_y
+        {
+            for x_index in 0..quarter.num_x
+            {
+                if let Some( kind ) = quarter.get( x_index, y_index )
+                {
+                    let ( cell_x, cell_y ) = ( get_x( x_index ), get_y( y_index ) );
+                    let image_id = sheetmap.map[( cell_y - BORDER ) % TILE_SHEET_NUM_DOWN][( cell_x - BORDER ) % TILE_SHEET_NUM_ACROSS];
+                    arena.set_cell( cell_x, cell_y, Some( image_id ) );
+                    arena.set_kind( cell_x, cell_y, mirror_kind( kind, flip_x, flip_y ) );
+                }
+            }
+        }
+        let ( spawn_x, spawn_y ) = quarter.spawn;
+        arena.spawns.push( Spawn
+        {
+            pos_x: ( get_x( spawn_x ) * TILE_SPACE + TILE_SIZE / 2 ) as u32,
+            pos_y: ( get_y( spawn_y ) * TILE_SPACE + TILE_SIZE / 2 ) as u32,
+            dir: if flip_y { Direction::UP } else { Direction::DOWN },
+        });
+    }
+    arena
+}
+
+fn get_spawn_cell( spawn: &Spawn ) -> ( usize, usize )
+{
+    ( spawn.pos_x as usize / TILE_SPACE, spawn.pos_y as usize / TILE_SPACE )
+}
+
+//walking distance from a cell to every floor cell, None where it can't be reached
+fn get_distances( arena: &Arena, start: ( usize, usize ) ) -> Vec<Option<usize>>
+{
+    let num_x = arena.get_num_tiles_x();
+    let num_y = arena.get_num_tiles_y();
+    let mut distances = vec![None; num_x * num_y];
+    let mut queue = VecDeque::new();
+    distances[start.1 * num_x + start.0] = Some( 0 );
+    queue.push_back( start );
+    while let Some( ( x_index, y_index ) ) = queue.pop_front()
+    {
+        let distance = distances[y_index * num_x + x_index].unwrap_or( 0 );
+        let neighbours = [
+            ( x_index.wrapping_sub( 1 ), y_index ),
+            ( x_index + 1, y_index ),
+            ( x_index, y_index.wrapping_sub( 1 ) ),
+            ( x_index, y_index + 1 ),
+        ];
+        for &( next_x, next_y ) in &neighbours
+        {
+            if next_x >= num_x || next_y >= num_y || arena.get_cell( next_x, next_y ).is_none() || distances[next_y * num_x + next_x].is_some()
+            {
+                continue;
+            }
+            distances[next_y * num_x + next_x] = Some( distance + 1 );
+            queue.push_back( ( next_x, next_y ) );
+        }
+    }
+    distances
+}
+
+//every robot has to be able to walk to every other one, and has as many floor tiles and as many
+//tiles it can pick up closer to it than to anyone else. floor no one can reach is taken away
+fn make_fair( mut arena: Arena, num_quarter_cells: usize ) -> Option<Arena>
+{
+    let num_x = arena.get_num_tiles_x();
+    let spawn_distances : Vec<Vec<Option<usize>>> = arena.spawns.iter()
+        .map(|spawn| get_distances( &arena, get_spawn_cell( spawn ) ))
+        .collect();
+    let connected = arena.spawns.iter().all(|spawn| {
+        let ( x_index, y_index ) = get_spawn_cell( spawn );
+        spawn_distances[0][y_index * num_x + x_index].is_some()
+    });
+    if !connected
+    {
+        return None;
+    }
+
+    let mut num_floor = 0;
+    let mut closest_floor = vec![0; arena.spawns.len()];
+    let mut closest_pickable = vec![0; arena.spawns.len()];
+    for y_index in 0..arena.get_num_tiles_y()
+    {
+        for x_index in 0..num_x
+        {
+            if arena.get_cell( x_index, y_index ).is_none()
+            {
+                continue;
+            }
+            let distances = match spawn_distances.iter().map(|distances| distances[y_index * num_x + x_index]).collect::<Option<Vec<usize>>>()
+            {
+                Some( distances ) => distances,
+                None =>
+                {
+                    arena.set_cell( x_index, y_index, None );
+                    continue;
+                }
+            };
+            num_floor += 1;
+            //cells halfway between robots belong to no one
+            let nearest = distances.iter().cloned().min().unwrap_or( 0 );
+            let mut closest = distances.iter().enumerate().filter(|&( _, distance )| *distance == nearest);
+            if let ( Some( ( index, _ ) ), None ) = ( closest.next(), closest.next() )
+            {
+                closest_floor[index] += 1;
+                if arena.get_kind( x_index, y_index ).can_pick_up()
+                {
+                    closest_pickable[index] += 1;
+                }
+            }
+        }
+    }
+    let fair = closest_floor.iter().all(|count| *count == closest_floor[0])
+        && closest_pickable.iter().all(|count| *count == closest_pickable[0]);
+    if fair && num_floor * 100 >= num_quarter_cells * 4 * MIN_FLOOR_PERCENT
+    {
+        Some( arena )
+    }
+    else
+    {
+        None
+    }
+}
+
+//the same seed always gives the same arena: symmetric, with islands joined by bridges, holes and
+//patches of special tiles, and fair to every spawn
+pub fn generate( seed: u64 ) -> Arena
+{
+    let name = get_generated_name( seed );
+    let mut rng = Rng::new( seed );
+    let num_x = MIN_QUARTER_X + rng.next_below( MAX_QUARTER_X - MIN_QUARTER_X + 1 );
+    let num_y = MIN_QUARTER_Y + rng.next_below( MAX_QUARTER_Y - MIN_QUARTER_Y + 1 );
+    for _ in 0..MAX_ATTEMPTS
+    {
+        let quarter = lay_out_quarter( &mut rng, num_x, num_y );
+        if let Some( arena ) = make_fair( build_arena( &name, &quarter ), num_x * num_y )
+        {
+            return arena;
+        }
+    }
+    build_arena( &name, &Quarter::new( num_x, num_y, ( 1, 1 ) ) )
+}
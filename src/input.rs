@@ -0,0 +1,70 @@
+const UP_BIT : u8 = 1;
+const DOWN_BIT : u8 = 2;
+const LEFT_BIT : u8 = 4;
+const RIGHT_BIT : u8 = 8;
+const ACTION_BIT : u8 = 16;
+
+//bytes one PlayerInput takes in replays and packets: the bits, then the stick
+pub const INPUT_SIZE : usize = 3;
+pub const STICK_MAX : i8 = 127;
+
+//what one player is doing during a single tick: held directions plus whether action was pressed.
+//a gamepad stick outside its dead zone moves the robot instead of the held directions
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct PlayerInput
+{
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub action: bool,
+    pub stick_x: i8,
+    pub stick_y: i8,
+}
+
+impl PlayerInput
+{
+    pub fn has_stick( &self ) -> bool
+    {
+        self.stick_x != 0 || self.stick_y != 0
+    }
+
+    pub fn to_bytes( &self ) -> [u8; INPUT_SIZE]
+    {
+        [ self.to_bits(), self.stick_x as u8, self.stick_y as u8 ]
+    }
+
+    //bytes must hold at least INPUT_SIZE bytes
+    pub fn from_bytes( bytes: &[u8] ) -> PlayerInput
+    {
+        let mut input = PlayerInput::from_bits( bytes[0] );
+        input.stick_x = bytes[1] as i8;
+        input.stick_y = bytes[2] as i8;
+        input
+    }
+
+    fn to_bits( &self ) -> u8
+    {
+        let mut bits = 0;
+        if self.up { bits |= UP_BIT; }
+        if self.down { bits |= DOWN_BIT; }
+        if self.left { bits |= LEFT_BIT; }
+        if self.right { bits |= RIGHT_BIT; }
+        if self.action { bits |= ACTION_BIT; }
+        bits
+    }
+
+    fn from_bits( bits: u8 ) -> PlayerInput
+    {
+        PlayerInput
+        {
+            up: bits & UP_BIT != 0,
+            down: bits & DOWN_BIT != 0,
+            left: bits & LEFT_BIT != 0,
+            right: bits & RIGHT_BIT != 0,
+            action: bits & ACTION_BIT != 0,
+            stick_x: 0,
+            stick_y: 0,
+        }
+    }
+}
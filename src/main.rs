@@ -4,34 +4,283 @@ extern crate ncollide2d;
 
 mod tilesheet;
 mod tile;
-mod hitbox;
+mod collision;
+mod physics;
+mod powerup;
+mod rng;
+mod game_mode;
 mod player;
 mod projectile;
 mod main_state;
 mod anim;
+mod game;
+mod render;
+mod input;
+mod replay;
+mod rollback;
+mod net;
+mod ai;
+mod shrink;
+mod arena;
+mod generator;
+mod editor;
+mod controls;
+mod gamepad;
+mod campaign;
 
 use main_state::*;
-use tile::*;
+use game::*;
+use replay::*;
+use net::*;
+use ai::*;
+use arena::*;
+use controls::*;
+use powerup::*;
+use game_mode::*;
+use campaign::*;
+use generator::*;
 
 use ggez::conf;
 use ggez::Context;
 use ggez::event;
 
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
 static GAME_TITLE: &'static str = "Tile League";
-pub const WINDOW_WIDTH : u32 = ( NUM_TILES_X * TILE_SPACE ) as u32;
-pub const WINDOW_HEIGHT : u32 = ( NUM_TILES_Y * TILE_SPACE ) as u32;
+const DEFAULT_POWERUP_INTERVAL_SECONDS : u32 = 8;
+
+fn get_arg<'a>( args: &'a [String], flag: &str ) -> Option<&'a String>
+{
+    let index = args.iter().position(|arg| arg == flag)?;
+    match args.get( index + 1 )
+    {
+        Some( value ) => Some( value ),
+        None =>
+        {
+            println!( "{} needs a value", flag );
+            process::exit( 1 );
+        }
+    }
+}
+
+fn get_number_arg<T: FromStr>( args: &[String], flag: &str, default: T ) -> T
+{
+    match get_arg( args, flag )
+    {
+        Some( value ) => value.parse().unwrap_or_else(|_| {
+            println!( "{} is not a valid number for {}", value, flag );
+            process::exit( 1 );
+        }),
+        None => default,
+    }
+}
+
+fn exit_on_error<T, E: fmt::Display>( result: Result<T, E>, what: &str ) -> T
+{
+    result.unwrap_or_else(|e| {
+        println!( "{}: {}", what, e );
+        process::exit( 1 );
+    })
+}
+
+fn has_flag( args: &[String], flag: &str ) -> bool
+{
+    args.iter().any(|arg| arg == flag)
+}
+
+//power-ups are off unless asked for, any of the power-up flags turns them on
+fn get_powerup_config( args: &[String] ) -> PowerupConfig
+{
+    let mut config = PowerupConfig::default();
+    if !has_flag( args, "--powerups" ) && get_arg( args, "--powerup-interval" ).is_none() && get_arg( args, "--powerup-pool" ).is_none()
+    {
+        return config;
+    }
+    let seconds = get_number_arg( args, "--powerup-interval", DEFAULT_POWERUP_INTERVAL_SECONDS ).max( 1 );
+    config.interval_ticks = seconds * TICKS_PER_SECOND;
+    if let Some( names ) = get_arg( args, "--powerup-pool" )
+    {
+        config.pool = names.split( ',' )
+            .map(|name| PowerupKind::from_name( name.trim() ).unwrap_or_else(|| {
+                println!( "{} is not a power-up, expected speed, double, shield, magnet or hover", name );
+                process::exit( 1 );
+            }))
+            .collect();
+        //the pool goes over the wire and into replays as bits, so repeats and order have to go here
+        //or the peer and the replay would pick from a different list than we do
+        let bits = config.get_pool_bits();
+        config.set_pool_bits( bits );
+    }
+    config
+}
+
+//picks up the saved progress, --stage replays any stage cleared so far or the next one
+fn get_campaign( args: &[String] ) -> Campaign
+{
+    let save = exit_on_error( CampaignSave::load_or_default(), "Could not load the campaign" );
+    let stage = match get_arg( args, "--stage" )
+    {
+        Some( _ ) => get_number_arg( args, "--stage", 1 ).max( 1 ) - 1,
+        None => save.get_next_stage(),
+    };
+    if !save.is_unlocked( stage )
+    {
+        println!( "Stage {} is locked, clear the ones before it first", stage + 1 );
+        process::exit( 1 );
+    }
+    for ( index, stage ) in STAGES.iter().enumerate()
+    {
+        match save.get_best_ticks( index )
+        {
+            Some( ticks ) => println!( "Stage {}: {}, best {:.1}s", index + 1, stage.name, ticks as f32 / TICKS_PER_SECOND as f32 ),
+            None if save.is_unlocked( index ) => println!( "Stage {}: {}", index + 1, stage.name ),
+            None => println!( "Stage {}: locked", index + 1 ),
+        }
+    }
+    Campaign::new( save, stage )
+}
+
+fn get_mode( args: &[String], config: &MatchConfig ) -> Mode
+{
+    if let Some( path ) = get_arg( args, "--replay" )
+    {
+        let replay = exit_on_error( Replay::load( Path::new( path ) ), "Could not load replay" );
+        return Mode::Replay( Playback::new( replay ) );
+    }
+
+    let conditions = LinkConditions
+    {
+        latency_ms: get_number_arg( args, "--latency", 0 ),
+        jitter_ms: get_number_arg( args, "--jitter", 0 ),
+        loss_percent: get_number_arg( args, "--loss", 0 ),
+    };
+    if let Some( port ) = get_arg( args, "--host" )
+    {
+        let port = exit_on_error( port.parse(), "Invalid port" );
+        let session = exit_on_error( NetSession::host( port, conditions, config.clone() ), "Could not host" );
+        println!( "Hosting on port {}, you are Player1", port );
+        return Mode::Online( session );
+    }
+    if let Some( address ) = get_arg( args, "--join" )
+    {
+        let remote = exit_on_error( address.parse(), "Invalid address, expected ip:port" );
+        let port = get_number_arg( args, "--port", 0 );
+        let mut session = exit_on_error( NetSession::join( port, remote, conditions ), "Could not join" );
+        println!( "Joining {}, waiting for the host", address );
+        session.wait_for_welcome();
+        println!( "Joined, you are Player2" );
+        return Mode::Online( session );
+    }
+    Mode::Local
+}
 
 pub fn main() {
+    let args : Vec<String> = env::args().collect();
+    let mut arena_name = get_arg( &args, "--arena" ).map_or( DEFAULT_ARENA.to_string(), |name| name.clone() );
+    if arena_name == GENERATED_ARENA
+    {
+        arena_name = get_generated_name( new_seed() % MAX_FRESH_SEED );
+    }
+    let edit = get_arg( &args, "--edit" );
+    let arena = match edit
+    {
+        //editing an arena that doesn't exist yet starts from the default one
+        Some( name ) => Arena::find( name ).unwrap_or_else(|_| {
+            let mut arena = Arena::new_default();
+            arena.name = name.clone();
+            arena
+        }),
+        None => exit_on_error( Arena::find( &arena_name ), "Could not load arena" ),
+    };
+    let mut config = MatchConfig::with_arena( new_seed(), get_number_arg( &args, "--players", MIN_PLAYERS ), arena );
+    config.shrink = has_flag( &args, "--shrink" );
+    config.knockback = has_flag( &args, "--knockback" );
+    config.ricochet = has_flag( &args, "--ricochet" );
+    config.powerups = get_powerup_config( &args );
+    if let Some( name ) = get_arg( &args, "--mode" )
+    {
+        config.mode = GameModeKind::from_name( name ).unwrap_or_else(|| {
+            println!( "{} is not a game mode, expected classic, king, hoard or survival", name );
+            process::exit( 1 );
+        });
+    }
+    config.teams = has_flag( &args, "--teams" );
+    config.friendly_fire = has_flag( &args, "--friendly-fire" );
+    config.hit_points = get_number_arg( &args, "--hit-points", 1 ).max( 1 ).min( MAX_HIT_POINTS );
+    config.lives = get_number_arg( &args, "--lives", 1 ).max( 1 ).min( MAX_LIVES );
+    config.rounds_to_win = get_number_arg( &args, "--rounds", DEFAULT_ROUNDS_TO_WIN ).max( 1 ).min( MAX_ROUNDS_TO_WIN );
+    //the campaign is played alone on this machine, with the stage's own arena and rules
+    let campaign = if has_flag( &args, "--campaign" ) { Some( get_campaign( &args ) ) } else { None };
+    if let Some( ref campaign ) = campaign
+    {
+        config = campaign.get_config( config.seed );
+    }
+    let mode = if campaign.is_some() { Mode::Local } else { get_mode( &args, &config ) };
+    let num_bots = get_number_arg( &args, "--bots", 0 );
+    let difficulty = match get_arg( &args, "--difficulty" )
+    {
+        Some( name ) => Difficulty::from_name( name ).unwrap_or_else(|| {
+            println!( "{} is not a difficulty, expected easy, normal or hard", name );
+            process::exit( 1 );
+        }),
+        None => Difficulty::Normal,
+    };
+    let controls = Controls::load_or_default().unwrap_or_else(|e| {
+        println!( "Could not load {}, using the default keys: {}", CONTROLS_FILE, e );
+        Controls::default()
+    });
     println!( "Welcome to Tile League!");
     println!( "Try to hit your opponent with floor tiles and avoid falling off!");
-    println!( "P1: WASD, Spacebar");
-    println!( "P2: Arrow Keys, Enter");
-    println!( "F5 to reset the game");
+    println!( "Action with empty hands next to another robot shoves it, maybe right into a hole!");
+    for ( index, binding ) in controls.bindings.iter().enumerate()
+    {
+        println!( "P{}: {}", index + 1, binding.get_help() );
+    }
+    println!( "F3 to rebind the keys, they are saved to {}", CONTROLS_FILE);
+    println!( "Gamepads join the first free player slot when plugged in: stick or d-pad to move, A or X for action");
+    println!( "Up to {} players with --players <count>", MAX_PLAYERS);
+    println!( "Play against the computer with --bots <count> --difficulty easy|normal|hard");
+    println!( "Play other arenas with --arena <name in arenas/ or file>");
+    println!( "A new symmetric arena every time with --arena random, or the same one again with the name it is given, e.g. --arena random-1234");
+    println!( "F2 or --edit <name> for the arena editor, arenas are saved to arenas/");
+    println!( "Make the floor crumble away over time with --shrink");
+    println!( "Tiles knock robots back instead of knocking them out with --knockback");
+    println!( "Thrown tiles land and fill holes when they run out of range, make them bounce off the walls with --ricochet");
+    println!( "Other ways to win with --mode king (hold the middle tile), hoard (bring tiles back to your base) or survival (dodge tiles falling from the sky)");
+    println!( "Odd players against even ones with --teams, add --friendly-fire to let teammates hit each other");
+    println!( "A gentler match with --hit-points <hits to knock out> and --lives <respawns + 1>");
+    println!( "Power-ups with --powerups, --powerup-interval <seconds> and --powerup-pool speed,double,shield,magnet,hover");
+    println!( "Play the single player stages with --campaign, progress is saved to {}, replay a cleared stage with --stage <number>", CAMPAIGN_SAVE_FILE);
+    println!( "Matches go to {} round wins, change it with --rounds <wins>", DEFAULT_ROUNDS_TO_WIN);
+    println!( "F5 to start a new match");
+    println!( "Matches are saved to replays/, play one back with --replay <file>");
+    println!( "Online: --host <port> or --join <ip:port>, test with --latency <ms> --jitter <ms> --loss <percent>");
     let mut c = conf::Conf::new();
     c.window_setup.title = GAME_TITLE.to_string();
-    c.window_mode.width = WINDOW_WIDTH;
-    c.window_mode.height = WINDOW_HEIGHT;
+    //the window fits the arena, a replay brings its own and so does the host of an online match
+    let arena = match mode
+    {
+        Mode::Replay( ref playback ) => &playback.get_replay().config.arena,
+        Mode::Online( ref session ) => &session.get_game().get_config().arena,
+        _ => &config.arena,
+    };
+    c.window_mode.width = arena.get_width();
+    c.window_mode.height = arena.get_height();
+    //a generated arena goes by its seed, worth passing on when the layout turns out to be a fun one
+    if parse_generated_name( &arena.name ).is_some()
+    {
+        println!( "Playing on {}, use that name with --arena to play it again", arena.name );
+        c.window_setup.title = format!( "{} - {}", GAME_TITLE, arena.name );
+    }
     let ctx = &mut Context::load_from_conf("Tile League", "ggez", c).unwrap();
-    let state = &mut MainState::new(ctx).unwrap();
+    let state = &mut MainState::new(ctx, mode, config, num_bots, difficulty, controls, campaign).unwrap();
+    if edit.is_some()
+    {
+        state.open_editor();
+    }
     event::run(ctx, state).unwrap();
 }
\ No newline at end of file
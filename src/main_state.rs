@@ -1,183 +1,808 @@
 use ggez::*;
 use ggez::event::*;
-use ggez::timer::*;
-use ggez::graphics::{DrawMode, Point2, Text};
-
-use player::*;
-use tile::*;
-use tilesheet::*;
-use projectile::*;
-use anim::*;
-
-pub const NUM_TILES_X : usize = TILE_SHEET_NUM_ACROSS + 6;
-pub const NUM_TILES_Y : usize = TILE_SHEET_NUM_DOWN + 6;
-
-const PLAYER_SPEED : f32 = 2.0;
-const EXPECTED_FRAME_RATE : f64 = 60.0;
-const EXPECTED_TIME_BETWEEN_FRAMES : f64 = 1.0/EXPECTED_FRAME_RATE;
-const PLAYER_SPAWN_OFFSET : u32 = 235;
-
-const P1_UP : event::Keycode = Keycode::W;
-const P1_DOWN : event::Keycode = Keycode::S;
-const P1_LEFT : event::Keycode = Keycode::A;
-const P1_RIGHT : event::Keycode = Keycode::D;
-const P1_ACTION : event::Keycode = Keycode::Space;
-
-const P2_UP : event::Keycode = Keycode::Up;
-const P2_DOWN : event::Keycode = Keycode::Down;
-const P2_LEFT : event::Keycode = Keycode::Left;
-const P2_RIGHT : event::Keycode = Keycode::Right;
-const P2_ACTION : event::Keycode = Keycode::Return;
-
-pub struct MainState 
+
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use game::*;
+use input::*;
+use render::*;
+use replay::*;
+use net::*;
+use ai::*;
+use editor::*;
+use controls::*;
+use gamepad::*;
+use campaign::*;
+
+const REPLAY_PAUSE : event::Keycode = Keycode::P;
+const REPLAY_STEP : event::Keycode = Keycode::Period;
+const REPLAY_FASTER : event::Keycode = Keycode::Equals;
+const REPLAY_SLOWER : event::Keycode = Keycode::Minus;
+
+const TOGGLE_EDITOR : event::Keycode = Keycode::F2;
+const EDITOR_FLOOR : event::Keycode = Keycode::Num1;
+const EDITOR_PAINT : event::Keycode = Keycode::Num2;
+const EDITOR_SPAWN : event::Keycode = Keycode::Num3;
+const EDITOR_KIND : event::Keycode = Keycode::Num4;
+const EDITOR_PREV_BRUSH : event::Keycode = Keycode::LeftBracket;
+const EDITOR_NEXT_BRUSH : event::Keycode = Keycode::RightBracket;
+const EDITOR_NEXT_SLOT : event::Keycode = Keycode::Tab;
+const EDITOR_ROTATE : event::Keycode = Keycode::R;
+const EDITOR_SAVE : event::Keycode = Keycode::S;
+
+const PAD_UP : event::Button = Button::DPadUp;
+const PAD_DOWN : event::Button = Button::DPadDown;
+const PAD_LEFT : event::Button = Button::DPadLeft;
+const PAD_RIGHT : event::Button = Button::DPadRight;
+const PAD_ACTION : event::Button = Button::A;
+const PAD_ALT_ACTION : event::Button = Button::X;
+
+const TOGGLE_REBIND : event::Keycode = Keycode::F3;
+const REBIND_LEAVE : event::Keycode = Keycode::Escape;
+
+pub fn new_seed() -> u64
 {
-    player1 : Player,
-    player2 : Player, 
-    tile_map: TileMap,
-    projectiles: Vec<Projectile>,
-    anims: Vec<Anim>, 
+    let now = SystemTime::now().duration_since( UNIX_EPOCH ).unwrap_or_default();
+    now.as_secs() << 32 ^ now.subsec_nanos() as u64
+}
+
+//how a round or match winner is announced
+fn get_side_name( game: &Game, side: u32 ) -> String
+{
+    if game.get_config().teams
+    {
+        format!( "Team{}", side )
+    }
+    else
+    {
+        format!( "Player{}", side )
+    }
+}
+
+//where the ticks come from: this keyboard, a replay file or a peer over the network
+pub enum Mode
+{
+    Local,
+    Replay( Playback ),
+    Online( NetSession ),
+}
+
+pub struct MainState
+{
+    mode: Mode,
+    game: Game,
+    assets: Assets,
+    font: graphics::Font,
+    small_font: graphics::Font,
     message: graphics::Text,
+    scoreboard: graphics::Text,
+    //what every new local match is set up with, apart from its seed
+    config: MatchConfig,
+    inputs: [PlayerInput; MAX_PLAYERS],
+    recording: Replay,
+    recording_saved: bool,
+    bots: Vec<Bot>,
+    difficulty: Difficulty,
+    //while editing the match is paused and every input goes to the editor
+    editor: Option<Editor>,
+    controls: Controls,
+    //while rebinding every key goes to the rebinding screen, local matches are paused
+    rebind: Option<Rebind>,
+    gamepads: Gamepads,
+    //playing through the campaign stages instead of free matches
+    campaign: Option<Campaign>,
 }
 
-impl MainState 
+impl MainState
 {
-    pub fn new(_ctx: &mut Context) -> GameResult<MainState> 
+    pub fn new(_ctx: &mut Context, mut mode: Mode, config: MatchConfig, num_bots: usize, difficulty: Difficulty, controls: Controls, campaign: Option<Campaign>) -> GameResult<MainState>
     {
         let bg_color = graphics::Color::new( 0.0, 0.0, 0.0, 1.0);
         graphics::set_background_color(_ctx, bg_color );
 
-        let font = graphics::Font::new(_ctx, "/DejaVuSansMono.ttf", 10)?;
-        let s = MainState 
-        { 
-            player1 : Player::new( _ctx, 1, ::WINDOW_WIDTH / 2, PLAYER_SPAWN_OFFSET, Direction::DOWN ),
-            player2 : Player::new( _ctx, 2, ::WINDOW_WIDTH / 2, ::WINDOW_HEIGHT - PLAYER_SPAWN_OFFSET, Direction::UP ),
-            tile_map: TileMap::new( _ctx, NUM_TILES_X, NUM_TILES_Y ),
-            projectiles: Vec::new(),
-            anims: Vec::new(),
-            message: graphics::Text::new(_ctx, "P1: wasd + spacebar, P2: arrow + enter.", &font)?
+        let font = graphics::Font::new(_ctx, "/DejaVuSansMono.ttf", 24)?;
+        let small_font = graphics::Font::new(_ctx, "/DejaVuSansMono.ttf", 10)?;
+        let game = match mode
+        {
+            Mode::Replay( ref mut playback ) => playback.new_game(),
+            _ => Game::new( config.clone() ),
+        };
+        //bots take the last slots so the keyboard bindings stay with the humans, the campaign is played alone
+        let num_bots = match mode
+        {
+            Mode::Local if campaign.is_none() => num_bots.min( game.players.len() ),
+            _ => 0,
+        };
+        let bots = ( game.players.len() - num_bots..game.players.len() )
+            .map(|index| Bot::new( index, difficulty ))
+            .collect();
+        let s = MainState
+        {
+            mode,
+            assets: Assets::new(_ctx)?,
+            message: graphics::Text::new(_ctx, "", &small_font)?,
+            scoreboard: graphics::Text::new(_ctx, "", &small_font)?,
+            font,
+            small_font,
+            config,
+            inputs: [PlayerInput::default(); MAX_PLAYERS],
+            recording: Replay::new( game.get_config().clone() ),
+            recording_saved: false,
+            bots,
+            difficulty,
+            editor: None,
+            controls,
+            rebind: None,
+            gamepads: Gamepads::new(),
+            campaign,
+            game,
         };
         Ok(s)
     }
 
-    fn reset( &mut self, _ctx: &mut Context)
+    fn reset( &mut self )
     {
-        self.player1 = Player::new( _ctx, 1, ::WINDOW_WIDTH / 2, PLAYER_SPAWN_OFFSET, Direction::DOWN );
-        self.player2 = Player::new( _ctx, 2, ::WINDOW_WIDTH / 2, ::WINDOW_HEIGHT - PLAYER_SPAWN_OFFSET, Direction::UP );
-        self.tile_map = TileMap::new( _ctx, NUM_TILES_X, NUM_TILES_Y );
-        self.projectiles = Vec::new();
-        self.anims = Vec::new();
+        match self.mode
+        {
+            Mode::Local => {}
+            Mode::Replay( ref mut playback ) =>
+            {
+                self.game = playback.new_game();
+                return;
+            }
+            //both sides would have to agree on a restart, so online matches are played once
+            Mode::Online( _ ) => { return; }
+        }
+        self.save_recording();
+        self.config.seed = new_seed();
+        if let Some( ref mut campaign ) = self.campaign
+        {
+            campaign.next();
+            self.config = campaign.get_config( self.config.seed );
+        }
+        self.game = Game::new( self.config.clone() );
+        self.inputs = [PlayerInput::default(); MAX_PLAYERS];
+        self.recording = Replay::new( self.game.get_config().clone() );
+        self.recording_saved = false;
+        for bot in &mut self.bots
+        {
+            *bot = Bot::new( bot.get_index(), self.difficulty );
+        }
     }
-}
 
-impl event::EventHandler for MainState 
-{
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> 
+    //only local matches can be edited, replays, online matches and the campaign have their arena fixed
+    pub fn open_editor( &mut self )
     {
+        if let ( &Mode::Local, None ) = ( &self.mode, &self.campaign )
+        {
+            println!( "Arena editor: left click edits, right click picks the brush from a tile");
+            println!( "1: floor tool, 2: paint tool, 3: spawn tool, 4: tile kind tool (cracked, ice, heavy, explosive, conveyors)");
+            println!( "[ and ] or the wheel: change brush");
+            println!( "Tab: select the next spawn, R: rotate it, Ctrl+S: save, F5 or F2: playtest");
+            self.editor = Some( Editor::new( self.config.arena.clone() ) );
+        }
+    }
 
-        let font = graphics::Font::new(_ctx, "/DejaVuSansMono.ttf", 24)?;
-        if self.player1.is_dead() && self.player2.is_dead() 
+    //leaves the editor and starts a fresh match on the edited arena
+    fn playtest( &mut self )
+    {
+        if let Some( editor ) = self.editor.take()
         {
-            self.message = graphics::Text::new(_ctx, "F5 to reset", &font)?;
+            //every other setting carries over, only the player count has to fit the new spawns
+            self.config.arena = editor.get_arena().clone();
+            self.config.num_players = self.config.num_players.min( self.config.arena.spawns.len() );
+            self.reset();
         }
-        else if self.player1.is_dead() 
+    }
+
+    fn get_editor_status_text( editor: &Editor ) -> String
+    {
+        let tool = match editor.get_tool()
         {
-            self.message = graphics::Text::new(_ctx, "Player2 wins. F5 to reset", &font)?;
-        } 
-        else if self.player2.is_dead()
+            Tool::Floor => format!( "floor, brush {}", editor.get_brush() ),
+            Tool::Paint => format!( "paint, brush {}", editor.get_brush() ),
+            Tool::Spawn => format!( "spawn P{}", editor.get_spawn_slot() + 1 ),
+            Tool::Kind => "tile kind".to_string(),
+        };
+        let saved = if editor.is_saved() { "" } else { " (unsaved)" };
+        format!( "Editing {}{} - {}. F5: playtest", editor.get_arena().name, saved, tool )
+    }
+
+    fn editor_key_down( &mut self, keycode: Keycode, keymod: Mod )
+    {
+        if let Some( ref mut editor ) = self.editor
         {
-            self.message = graphics::Text::new(_ctx, "Player1 wins. F5 to reset", &font)?;
-        } 
+            match keycode
+            {
+                EDITOR_FLOOR => { editor.set_tool( Tool::Floor ) }
+                EDITOR_PAINT => { editor.set_tool( Tool::Paint ) }
+                EDITOR_SPAWN => { editor.set_tool( Tool::Spawn ) }
+                EDITOR_KIND => { editor.set_tool( Tool::Kind ) }
+                EDITOR_PREV_BRUSH => { editor.next_brush( -1 ) }
+                EDITOR_NEXT_BRUSH => { editor.next_brush( 1 ) }
+                EDITOR_NEXT_SLOT => { editor.next_spawn_slot() }
+                EDITOR_ROTATE => { editor.rotate_spawn() }
+                EDITOR_SAVE if keymod.intersects( LCTRLMOD | RCTRLMOD ) =>
+                {
+                    match editor.save()
+                    {
+                        Ok( path ) => println!( "Arena saved to {}", path.display() ),
+                        Err( e ) => println!( "Could not save arena: {}", e ),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    //replays are watched, not played, so there is nothing to rebind there
+    fn open_rebind( &mut self )
+    {
+        if let Mode::Replay( _ ) = self.mode
+        {
+            return;
+        }
+        //keys held now would never see their release
+        self.inputs = [PlayerInput::default(); MAX_PLAYERS];
+        self.rebind = Some( Rebind::new( self.controls.clone() ) );
+    }
+
+    fn rebind_key_down( &mut self, keycode: Keycode )
+    {
+        if keycode == REBIND_LEAVE || keycode == TOGGLE_REBIND
+        {
+            self.rebind = None;
+            return;
+        }
+        let done = match self.rebind
+        {
+            Some( ref mut rebind ) => rebind.key_down( keycode ),
+            None => false,
+        };
+        if !done
+        {
+            return;
+        }
+        if let Some( ref rebind ) = self.rebind
+        {
+            self.controls = rebind.get_controls().clone();
+        }
+        match self.controls.save( Path::new( CONTROLS_FILE ) )
+        {
+            Ok( () ) => println!( "Controls saved to {}", CONTROLS_FILE ),
+            Err( e ) => println!( "Could not save controls: {}", e ),
+        }
+    }
+
+    fn is_paused( &self ) -> bool
+    {
+        match self.mode
+        {
+            Mode::Local => self.editor.is_some() || self.rebind.is_some(),
+            _ => false,
+        }
+    }
+
+    fn save_recording( &mut self )
+    {
+        if self.recording_saved
+        {
+            return;
+        }
+        let recording = match self.mode
+        {
+            Mode::Local =>
+            {
+                self.recording.finish( &self.game );
+                &self.recording
+            }
+            Mode::Online( ref mut session ) =>
+            {
+                match session.get_rollback_mut()
+                {
+                    Some( rollback ) =>
+                    {
+                        let confirmed = rollback.get_confirmed().clone();
+                        let recording = rollback.get_recording_mut();
+                        recording.finish( &confirmed );
+                        &*recording
+                    }
+                    None => { return; }
+                }
+            }
+            Mode::Replay( _ ) => { return; }
+        };
+        if recording.num_ticks() == 0
+        {
+            return;
+        }
+        self.recording_saved = true;
+        match recording.save_to_replay_dir()
+        {
+            Ok( path ) => println!( "Replay saved to {}", path.display() ),
+            Err( e ) => println!( "Could not save replay: {}", e ),
+        }
+    }
+
+    fn tick( &mut self )
+    {
+        let decided = match self.mode
+        {
+            Mode::Local =>
+            {
+                for bot in &mut self.bots
+                {
+                    self.inputs[bot.get_index()] = bot.think( &self.game );
+                }
+                let inputs = &mut self.inputs[..self.game.players.len()];
+                if !self.recording_saved
+                {
+                    self.recording.record( inputs );
+                }
+                self.game.tick( inputs );
+                for input in inputs.iter_mut()
+                {
+                    input.action = false;
+                }
+                if let Some( ref mut campaign ) = self.campaign
+                {
+                    campaign.update( &self.game );
+                }
+                self.game.get_outcome() != Outcome::InProgress
+            }
+            Mode::Replay( ref mut playback ) =>
+            {
+                playback.update( &mut self.game );
+                false
+            }
+            Mode::Online( ref mut session ) =>
+            {
+                if session.update( self.inputs[0] )
+                {
+                    self.inputs[0].action = false;
+                }
+                //only what both sides agreed on counts, the predicted game may still roll back
+                match session.get_rollback_mut()
+                {
+                    Some( rollback ) => rollback.get_confirmed().get_outcome() != Outcome::InProgress,
+                    None => false,
+                }
+            }
+        };
+        if decided
+        {
+            self.save_recording();
+        }
+    }
+
+    fn get_game( &self ) -> &Game
+    {
+        match self.mode
+        {
+            Mode::Online( ref session ) => session.get_game(),
+            _ => &self.game,
+        }
+    }
+
+    fn get_status_text( &self ) -> String
+    {
+        match self.mode
+        {
+            Mode::Replay( ref playback ) =>
+            {
+                let mut status = format!( "Replay tick {}/{} at {}x", playback.get_next_tick(), playback.get_replay().num_ticks(), playback.get_speed() );
+                if playback.is_desynced( &self.game )
+                {
+                    status += " - DESYNCED";
+                }
+                else if playback.is_finished()
+                {
+                    status += " - finished";
+                }
+                else if playback.is_paused()
+                {
+                    status += " - paused";
+                }
+                return status + ". P: pause, .: step, +/-: speed, F5: restart";
+            }
+            Mode::Online( ref session ) =>
+            {
+                if !session.is_connected()
+                {
+                    return "Waiting for the other player...".to_string();
+                }
+                if session.is_desynced()
+                {
+                    return "Desynced from the other player!".to_string();
+                }
+            }
+            Mode::Local => {}
+        }
+        let game = self.get_game();
+        if let Some( ref campaign ) = self.campaign
+        {
+            return campaign.get_status_text( game ).unwrap_or_else(|| self.get_controls_help());
+        }
+        if let Outcome::Winner( id ) = game.get_outcome()
+        {
+            let again = match self.mode
+            {
+                Mode::Local => " F5 for a new one",
+                _ => "",
+            };
+            return format!( "{} wins the match!{}", get_side_name( game, id ), again );
+        }
+        match game.get_round_result()
+        {
+            Some( Outcome::Winner( id ) ) => format!( "{} takes round {}", get_side_name( game, id ), game.get_round() ),
+            Some( _ ) => format!( "Round {} is a draw, again!", game.get_round() ),
+            None => self.get_controls_help(),
+        }
+    }
+
+    fn get_scoreboard_text( &self ) -> String
+    {
+        let game = self.get_game();
+        let config = game.get_config();
+        //a stage is a single round, all there is to show is how it is going
+        if self.campaign.is_some()
+        {
+            return game.get_mode().get_hud_text( config );
+        }
+        let scores : Vec<String> = game.get_scores().iter()
+            .enumerate()
+            .map(|( index, score )| {
+                //what the side's robots have left this round, when there is more than one of it
+                let mut health = String::new();
+                for player in game.players.iter().filter(|player| !player.is_dead() && config.get_team( player.get_id() ) == index as u32 + 1)
+                {
+                    if config.teams && ( config.lives > 1 || config.hit_points > 1 ) { health += &format!( " P{}", player.get_id() ); }
+                    if config.lives > 1 { health += &format!( " L{}", player.get_lives() ); }
+                    if config.hit_points > 1 { health += &format!( " HP{}", player.get_hit_points() ); }
+                }
+                format!( "{}{}: {}{}", config.get_side_prefix(), index + 1, score, health )
+            })
+            .collect();
+        let text = format!( "Round {} - {} - first to {}", game.get_round(), scores.join( "  " ), config.rounds_to_win );
+        //the game mode keeps its own score within the round
+        let mode_text = game.get_mode().get_hud_text( config );
+        if mode_text.is_empty()
+        {
+            text
+        }
         else
         {
-            let smaller_font = graphics::Font::new(_ctx, "/DejaVuSansMono.ttf", 10)?;
-            self.message = graphics::Text::new(_ctx, "P1: wasd + spacebar, P2: arrow + enter.", &smaller_font)?;
+            format!( "{} - {}", text, mode_text )
+        }
+    }
+
+    fn is_match_over( &self ) -> bool
+    {
+        match self.mode
+        {
+            Mode::Replay( _ ) => false,
+            _ => self.get_game().get_outcome() != Outcome::InProgress,
+        }
+    }
+
+    fn get_controls_help( &self ) -> String
+    {
+        let num_bindings = match self.mode
+        {
+            Mode::Online( _ ) => 2,
+            _ => self.game.players.len(),
+        };
+        let help : Vec<String> = self.controls.bindings.iter()
+            .take( num_bindings )
+            .enumerate()
+            .map(|( index, binding )| {
+                if self.bots.iter().any(|bot| bot.get_index() == index)
+                {
+                    format!( "P{}: cpu ({:?})", index + 1, self.difficulty )
+                }
+                else if self.gamepads.has_pad( index )
+                {
+                    format!( "P{}: {} or gamepad", index + 1, binding.get_help() )
+                }
+                else
+                {
+                    format!( "P{}: {}", index + 1, binding.get_help() )
+                }
+            })
+            .collect();
+        help.join( ", " ) + "."
+    }
+
+    fn set_held( &mut self, keycode: Keycode, held: bool )
+    {
+        let online = match self.mode
+        {
+            Mode::Online( _ ) => true,
+            _ => false,
+        };
+        for ( index, binding ) in self.controls.bindings.iter().enumerate()
+        {
+            //online, the first two bindings both drive our own robot, which is always slot 0
+            let input = if !online
+            {
+                &mut self.inputs[index]
+            }
+            else if index < 2
+            {
+                &mut self.inputs[0]
+            }
+            else
+            {
+                continue;
+            };
+            if keycode == binding.up { input.up = held }
+            else if keycode == binding.down { input.down = held }
+            else if keycode == binding.left { input.left = held }
+            else if keycode == binding.right { input.right = held }
+            else if keycode == binding.action && held { input.action = true }
+        }
+    }
+
+    //the player slots a gamepad can drive: every human one locally, only our own robot online
+    fn get_pad_slots( &self ) -> Vec<usize>
+    {
+        match self.mode
+        {
+            Mode::Local => ( 0..self.game.players.len() )
+                .filter(|index| !self.bots.iter().any(|bot| bot.get_index() == *index))
+                .collect(),
+            Mode::Online( _ ) => vec![0],
+            Mode::Replay( _ ) => Vec::new(),
         }
+    }
 
-        let delta = (timer::duration_to_f64(timer::get_delta(_ctx))) as f32;
-        let factor = delta / (EXPECTED_TIME_BETWEEN_FRAMES) as f32;
+    fn update_gamepads( &mut self, ctx: &Context )
+    {
+        let slots = self.get_pad_slots();
+        for change in self.gamepads.update( ctx, &slots )
+        {
+            match change
+            {
+                Hotplug::Connected( slot, name ) => println!( "{} is playing as P{}", name, slot + 1 ),
+                Hotplug::Disconnected( slot ) =>
+                {
+                    println!( "The gamepad of P{} was disconnected", slot + 1 );
+                    self.inputs[slot] = PlayerInput::default();
+                }
+            }
+        }
+    }
 
-        self.player1.update( _ctx, &mut self.projectiles, &mut self.anims, &self.tile_map, factor );
-        self.player2.update( _ctx, &mut self.projectiles, &mut self.anims, &self.tile_map, factor );
-        for ref mut projectile in &mut self.projectiles
+    //pads only play, the editor and the rebinding screen stay on mouse and keyboard
+    fn get_pad_input( &mut self, instance_id: i32 ) -> Option<&mut PlayerInput>
+    {
+        if self.editor.is_some() || self.rebind.is_some()
         {
-            projectile.update( _ctx, factor, &mut self.anims);
-        } 
-        self.projectiles.retain(|projectile| {
-            !projectile.is_dead()
-        });
-        for ref mut anim in &mut self.anims
+            return None;
+        }
+        let slot = self.gamepads.get_slot( instance_id )?;
+        Some( &mut self.inputs[slot] )
+    }
+
+    fn replay_key_down( &mut self, keycode: Keycode )
+    {
+        if let Mode::Replay( ref mut playback ) = self.mode
         {
-            anim.update( );
+            match keycode
+            {
+                REPLAY_PAUSE => { playback.toggle_pause() }
+                REPLAY_STEP => { if playback.is_paused() { playback.step( &mut self.game ) } }
+                REPLAY_FASTER | Keycode::KpPlus => { playback.faster() }
+                REPLAY_SLOWER | Keycode::KpMinus => { playback.slower() }
+                _ => {}
+            }
         }
+    }
+}
 
-        self.anims.retain(|anim| {!anim.is_dead()});
+impl event::EventHandler for MainState
+{
+    fn update(&mut self, _ctx: &mut Context) -> GameResult<()>
+    {
+        self.update_gamepads(_ctx);
+        if self.is_paused()
+        {
+            //keep the tick clock drained so the match doesn't race to catch up after editing
+            while timer::check_update_time(_ctx, TICKS_PER_SECOND) {}
+        }
+        else
+        {
+            while timer::check_update_time(_ctx, TICKS_PER_SECOND)
+            {
+                self.tick();
+            }
+        }
+        if let Some( ref editor ) = self.editor
+        {
+            self.message = graphics::Text::new(_ctx, &MainState::get_editor_status_text( editor ), &self.small_font)?;
+            return Ok(());
+        }
+        if let Some( ref rebind ) = self.rebind
+        {
+            self.message = graphics::Text::new(_ctx, &rebind.get_prompt(), &self.small_font)?;
+            return Ok(());
+        }
 
+        let big_text = match self.mode
+        {
+            Mode::Replay( _ ) => false,
+            _ => self.get_game().get_round_result().is_some(),
+        };
+        let font = if big_text { &self.font } else { &self.small_font };
+        self.message = graphics::Text::new(_ctx, &self.get_status_text(), font)?;
+        let font = if self.is_match_over() { &self.font } else { &self.small_font };
+        self.scoreboard = graphics::Text::new(_ctx, &self.get_scoreboard_text(), font)?;
 
         Ok(())
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> 
+    fn draw(&mut self, ctx: &mut Context) -> GameResult<()>
    {
         graphics::clear(ctx);
-        /*let bg_pos = graphics::Point2::new( 40.0, 45.0);
-        let background = graphics::Image::new( ctx, "/border.png" ).unwrap();
-        graphics::draw( ctx, &background, bg_pos, 0.0 );
-*/
 
-        self.tile_map.draw( ctx );
-        self.player1.draw( ctx );
-        self.player2.draw( ctx );
-        for ref mut projectile in &mut self.projectiles
+        let remaining = timer::duration_to_f64(timer::get_remaining_update_time(ctx));
+        let alpha = ( remaining * TICKS_PER_SECOND as f64 ) as f32;
+        let game = match self.mode
         {
-            projectile.draw( ctx );
-        }
-        for ref mut anim in &mut self.anims
+            Mode::Online( ref session ) => session.get_game(),
+            _ => &self.game,
+        };
+        match self.editor
         {
-            anim.draw(ctx);
+            Some( ref editor ) => draw_editor( ctx, &self.assets, editor )?,
+            None => draw_game( ctx, &mut self.assets, game, alpha )?,
         }
 
+        //the final result gets the screen to itself, and so does the rebinding screen
+        if self.editor.is_none() && ( self.is_match_over() || self.rebind.is_some() )
+        {
+            draw_shade( ctx )?;
+        }
         let dest_point = graphics::Point2::new(10.0, 10.0);
         graphics::draw(ctx, &self.message, dest_point, 0.0)?;
+        if self.editor.is_none() && self.rebind.is_none()
+        {
+            let dest_point = graphics::Point2::new(10.0, 10.0 + self.message.height() as f32 + 6.0);
+            graphics::draw(ctx, &self.scoreboard, dest_point, 0.0)?;
+        }
         graphics::present(ctx);
         Ok(())
     }
 
-    fn key_down_event( &mut self, ctx: &mut Context, keycode: Keycode, _: Mod, _: bool )
+    fn key_down_event( &mut self, _ctx: &mut Context, keycode: Keycode, keymod: Mod, _: bool )
+    {
+        if self.editor.is_some()
+        {
+            if keycode == Keycode::F5 || keycode == TOGGLE_EDITOR
+            {
+                self.playtest();
+            }
+            else
+            {
+                self.editor_key_down( keycode, keymod );
+            }
+            return;
+        }
+        if self.rebind.is_some()
+        {
+            self.rebind_key_down( keycode );
+            return;
+        }
+        if keycode == TOGGLE_EDITOR
+        {
+            self.open_editor();
+            return;
+        }
+        if keycode == TOGGLE_REBIND
+        {
+            self.open_rebind();
+            return;
+        }
+        if keycode == Keycode::F5
+        {
+            self.reset();
+            return;
+        }
+        if let Mode::Replay( _ ) = self.mode
+        {
+            self.replay_key_down( keycode );
+            return;
+        }
+        self.set_held( keycode, true );
+    }
+
+    fn key_up_event( &mut self, _ctx: &mut Context, keycode: Keycode, _: Mod, _: bool )
+    {
+        self.set_held( keycode, false );
+    }
+
+    fn mouse_button_down_event( &mut self, _ctx: &mut Context, button: MouseButton, x: i32, y: i32 )
+    {
+        if let Some( ref mut editor ) = self.editor
+        {
+            match button
+            {
+                MouseButton::Left => editor.press( x, y ),
+                MouseButton::Right => editor.pick( x, y ),
+                _ => {}
+            }
+        }
+    }
+
+    fn mouse_button_up_event( &mut self, _ctx: &mut Context, _: MouseButton, _: i32, _: i32 )
+    {
+        if let Some( ref mut editor ) = self.editor
+        {
+            editor.release();
+        }
+    }
+
+    fn mouse_motion_event( &mut self, _ctx: &mut Context, _: MouseState, x: i32, y: i32, _: i32, _: i32 )
+    {
+        if let Some( ref mut editor ) = self.editor
+        {
+            editor.move_cursor( x, y );
+        }
+    }
+
+    fn mouse_wheel_event( &mut self, _ctx: &mut Context, _: i32, y: i32 )
     {
-        match keycode
+        if let Some( ref mut editor ) = self.editor
         {
-            P1_UP => { self.player1.set_vel_y( -PLAYER_SPEED ) }
-            P1_DOWN => { self.player1.set_vel_y( PLAYER_SPEED ) }
-            P1_LEFT => { self.player1.set_vel_x( -PLAYER_SPEED ) }
-            P1_RIGHT => { self.player1.set_vel_x( PLAYER_SPEED ) }
-            P2_UP => { self.player2.set_vel_y( -PLAYER_SPEED ) }
-            P2_DOWN => { self.player2.set_vel_y( PLAYER_SPEED ) }
-            P2_LEFT => { self.player2.set_vel_x( -PLAYER_SPEED ) }
-            P2_RIGHT => { self.player2.set_vel_x( PLAYER_SPEED ) }
+            editor.next_brush( y.signum() );
+        }
+    }
 
-            P1_ACTION => { self.player1.on_action( ctx, &mut self.tile_map, &mut self.projectiles ) }
-            P2_ACTION => { self.player2.on_action( ctx, &mut self.tile_map, &mut self.projectiles ) }
-            
-            Keycode::F5 => { self.reset( ctx ); }
+    fn controller_button_down_event( &mut self, _ctx: &mut Context, button: Button, instance_id: i32 )
+    {
+        if let Some( input ) = self.get_pad_input( instance_id )
+        {
+            match button
+            {
+                PAD_UP => { input.up = true }
+                PAD_DOWN => { input.down = true }
+                PAD_LEFT => { input.left = true }
+                PAD_RIGHT => { input.right = true }
+                PAD_ACTION | PAD_ALT_ACTION => { input.action = true }
+                _ => {}
+            }
+        }
+    }
 
-            _ => {}
+    fn controller_button_up_event( &mut self, _ctx: &mut Context, button: Button, instance_id: i32 )
+    {
+        if let Some( input ) = self.get_pad_input( instance_id )
+        {
+            match button
+            {
+                PAD_UP => { input.up = false }
+                PAD_DOWN => { input.down = false }
+                PAD_LEFT => { input.left = false }
+                PAD_RIGHT => { input.right = false }
+                _ => {}
+            }
         }
     }
 
-    fn key_up_event( &mut self, ctx: &mut Context, keycode: Keycode, _: Mod, _: bool )
+    fn controller_axis_event( &mut self, _ctx: &mut Context, axis: Axis, value: i16, instance_id: i32 )
     {
-        match keycode
+        //the stick is tracked even while editing so it is right again afterwards
+        if let Some( ( slot, ( stick_x, stick_y ) ) ) = self.gamepads.on_axis( instance_id, axis, value )
         {
-            P1_UP => { self.player1.on_dir_released(Direction::UP) }
-            P1_DOWN => { self.player1.on_dir_released(Direction::DOWN) }
-            P1_LEFT => { self.player1.on_dir_released(Direction::LEFT)}
-            P1_RIGHT => { self.player1.on_dir_released(Direction::RIGHT)}
-            P2_UP => { self.player2.on_dir_released(Direction::UP)}
-            P2_DOWN => { self.player2.on_dir_released(Direction::DOWN) }
-            P2_LEFT => { self.player2.on_dir_released(Direction::LEFT) }
-            P2_RIGHT => { self.player2.on_dir_released(Direction::RIGHT)}
-            _ => {}
+            self.inputs[slot].stick_x = stick_x;
+            self.inputs[slot].stick_y = stick_y;
         }
-        
     }
-}
\ No newline at end of file
+
+    fn quit_event( &mut self, _ctx: &mut Context ) -> bool
+    {
+        self.save_recording();
+        false
+    }
+}
@@ -0,0 +1,529 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use game::*;
+use arena::*;
+use input::*;
+use rollback::*;
+use game_mode::*;
+
+const PACKET_MAGIC : u8 = b'T';
+const HELLO : u8 = 0;
+const WELCOME : u8 = 1;
+const INPUTS : u8 = 2;
+
+//big enough for the welcome, which carries the whole arena
+const MAX_PACKET_SIZE : usize = 8192;
+const MAX_INPUTS_PER_PACKET : usize = 64;
+const HELLO_INTERVAL_MS : u64 = 200;
+const WELCOME_POLL_MS : u64 = 10;
+
+const ONLINE_PLAYERS : usize = 2;
+const HOST_INDEX : usize = 0;
+const JOIN_INDEX : usize = 1;
+
+const FLAG_SHRINK : u8 = 1;
+const FLAG_KNOCKBACK : u8 = 2;
+const FLAG_RICOCHET : u8 = 4;
+const FLAG_TEAMS : u8 = 8;
+const FLAG_FRIENDLY_FIRE : u8 = 16;
+
+//artificial network trouble applied to everything we send, for testing on localhost
+#[derive(Clone, Default)]
+pub struct LinkConditions
+{
+    pub latency_ms: u32,
+    pub jitter_ms: u32,
+    pub loss_percent: u32,
+}
+
+//a udp socket talking to one peer, with outgoing packets delayed and dropped per LinkConditions
+pub struct Link
+{
+    socket: UdpSocket,
+    remote: Option<SocketAddr>,
+    conditions: LinkConditions,
+    outgoing: Vec<( Instant, Vec<u8> )>,
+    noise: u64,
+}
+
+impl Link
+{
+    pub fn bind( port: u16, remote: Option<SocketAddr>, conditions: LinkConditions ) -> io::Result<Link>
+    {
+        let socket = UdpSocket::bind( ( "0.0.0.0", port ) )?;
+        socket.set_nonblocking( true )?;
+        Ok( Link
+        {
+            socket,
+            remote,
+            conditions,
+            outgoing: Vec::new(),
+            noise: 0x9e3779b97f4a7c15,
+        })
+    }
+
+    //xorshift, only used to fake a bad network so it stays out of the game's own randomness
+    fn next_noise( &mut self ) -> u32
+    {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 7;
+        self.noise ^= self.noise << 17;
+        ( self.noise >> 32 ) as u32
+    }
+
+    pub fn send( &mut self, packet: Vec<u8> )
+    {
+        if self.conditions.loss_percent > 0 && self.next_noise() % 100 < self.conditions.loss_percent
+        {
+            return;
+        }
+        let mut delay = self.conditions.latency_ms;
+        if self.conditions.jitter_ms > 0
+        {
+            delay += self.next_noise() % ( self.conditions.jitter_ms + 1 );
+        }
+        let due = Instant::now() + Duration::from_millis( delay as u64 );
+        self.outgoing.push( ( due, packet ) );
+    }
+
+    //actually sends the packets whose artificial delay is over
+    pub fn flush( &mut self )
+    {
+        let now = Instant::now();
+        if let Some( remote ) = self.remote
+        {
+            let socket = &self.socket;
+            self.outgoing.retain(|&( due, ref packet )| {
+                if due > now
+                {
+                    return true;
+                }
+                let _ = socket.send_to( packet, remote );
+                false
+            });
+        }
+    }
+
+    //sends whatever is due and returns every packet that arrived from the peer
+    pub fn poll( &mut self ) -> Vec<Vec<u8>>
+    {
+        self.flush();
+        let mut received = Vec::new();
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        loop
+        {
+            match self.socket.recv_from( &mut buf )
+            {
+                Ok( ( len, from ) ) =>
+                {
+                    if self.remote.is_none()
+                    {
+                        self.remote = Some( from );
+                    }
+                    if Some( from ) == self.remote
+                    {
+                        received.push( buf[..len].to_vec() );
+                    }
+                }
+                Err( _ ) => break,
+            }
+        }
+        received
+    }
+}
+
+pub enum NetRole
+{
+    Host,
+    Join,
+}
+
+enum Packet
+{
+    Hello,
+    Welcome( MatchConfig ),
+    Inputs
+    {
+        ack: usize,
+        start: usize,
+        inputs: Vec<PlayerInput>,
+        checksum: Option<( usize, u64 )>,
+    },
+}
+
+fn push_u32( packet: &mut Vec<u8>, value: u32 )
+{
+    for shift in 0..4
+    {
+        packet.push( ( value >> ( shift * 8 ) ) as u8 );
+    }
+}
+
+fn push_u64( packet: &mut Vec<u8>, value: u64 )
+{
+    push_u32( packet, value as u32 );
+    push_u32( packet, ( value >> 32 ) as u32 );
+}
+
+fn push_string( packet: &mut Vec<u8>, value: &str )
+{
+    packet.push( value.len() as u8 );
+    packet.push( ( value.len() >> 8 ) as u8 );
+    packet.extend( value.as_bytes() );
+}
+
+fn get_u32( bytes: &[u8], at: usize ) -> Option<u32>
+{
+    let bytes = bytes.get( at..at + 4 )?;
+    Some( bytes.iter().rev().fold( 0, |acc, byte| acc << 8 | *byte as u32 ) )
+}
+
+fn get_u64( bytes: &[u8], at: usize ) -> Option<u64>
+{
+    let low = get_u32( bytes, at )? as u64;
+    let high = get_u32( bytes, at + 4 )? as u64;
+    Some( low | high << 32 )
+}
+
+//the string and the offset just past it
+fn get_string( bytes: &[u8], at: usize ) -> Option<( String, usize )>
+{
+    let len = *bytes.get( at )? as usize | ( *bytes.get( at + 1 )? as usize ) << 8;
+    let value = String::from_utf8( bytes.get( at + 2..at + 2 + len )?.to_vec() ).ok()?;
+    Some( ( value, at + 2 + len ) )
+}
+
+impl Packet
+{
+    fn encode( &self ) -> Vec<u8>
+    {
+        let mut packet = vec![PACKET_MAGIC];
+        match *self
+        {
+            Packet::Hello => { packet.push( HELLO ); }
+            Packet::Welcome( ref config ) =>
+            {
+                packet.push( WELCOME );
+                push_u64( &mut packet, config.seed );
+                let mut flags = 0;
+                if config.shrink { flags |= FLAG_SHRINK; }
+                if config.knockback { flags |= FLAG_KNOCKBACK; }
+                if config.ricochet { flags |= FLAG_RICOCHET; }
+                if config.teams { flags |= FLAG_TEAMS; }
+                if config.friendly_fire { flags |= FLAG_FRIENDLY_FIRE; }
+                packet.push( flags );
+                packet.push( config.rounds_to_win as u8 );
+                packet.push( config.hit_points as u8 );
+                packet.push( config.lives as u8 );
+                packet.push( config.mode.get_index() as u8 );
+                push_string( &mut packet, &config.arena.name );
+                push_string( &mut packet, &config.arena.to_text() );
+                push_u32( &mut packet, config.powerups.interval_ticks );
+                packet.push( config.powerups.max_on_floor as u8 );
+                packet.push( config.powerups.get_pool_bits() );
+            }
+            Packet::Inputs { ack, start, ref inputs, checksum } =>
+            {
+                packet.push( INPUTS );
+                push_u32( &mut packet, ack as u32 );
+                push_u32( &mut packet, start as u32 );
+                let ( checksum_tick, checksum ) = checksum.unwrap_or( ( 0, 0 ) );
+                push_u32( &mut packet, checksum_tick as u32 );
+                push_u64( &mut packet, checksum );
+                packet.push( inputs.len() as u8 );
+                for input in inputs
+                {
+                    packet.extend_from_slice( &input.to_bytes() );
+                }
+            }
+        }
+        packet
+    }
+
+    fn decode( bytes: &[u8] ) -> Option<Packet>
+    {
+        if bytes.len() < 2 || bytes[0] != PACKET_MAGIC
+        {
+            return None;
+        }
+        match bytes[1]
+        {
+            HELLO => Some( Packet::Hello ),
+            WELCOME =>
+            {
+                let seed = get_u64( bytes, 2 )?;
+                let flags = *bytes.get( 10 )?;
+                let rounds_to_win = *bytes.get( 11 )? as u32;
+                let hit_points = *bytes.get( 12 )? as u32;
+                let lives = *bytes.get( 13 )? as u32;
+                if rounds_to_win == 0 || hit_points == 0 || lives == 0
+                {
+                    return None;
+                }
+                let mode = GameModeKind::from_index( *bytes.get( 14 )? as usize )?;
+                let ( name, at ) = get_string( bytes, 15 )?;
+                let ( text, at ) = get_string( bytes, at )?;
+                let interval_ticks = get_u32( bytes, at )?;
+                let max_on_floor = *bytes.get( at + 4 )? as usize;
+                let pool_bits = *bytes.get( at + 5 )?;
+                let arena = Arena::parse( &name, &text ).ok()?;
+                let mut config = MatchConfig::with_arena( seed, ONLINE_PLAYERS, arena );
+                config.shrink = flags & FLAG_SHRINK != 0;
+                config.knockback = flags & FLAG_KNOCKBACK != 0;
+                config.ricochet = flags & FLAG_RICOCHET != 0;
+                config.teams = flags & FLAG_TEAMS != 0;
+                config.friendly_fire = flags & FLAG_FRIENDLY_FIRE != 0;
+                config.powerups.interval_ticks = interval_ticks;
+                config.powerups.max_on_floor = max_on_floor;
+                config.powerups.set_pool_bits( pool_bits );
+                config.rounds_to_win = rounds_to_win;
+                config.hit_points = hit_points;
+                config.lives = lives;
+                config.mode = mode;
+                Some( Packet::Welcome( config ) )
+            }
+            INPUTS =>
+            {
+                let ack = get_u32( bytes, 2 )? as usize;
+                let start = get_u32( bytes, 6 )? as usize;
+                let checksum_tick = get_u32( bytes, 10 )? as usize;
+                let checksum = get_u64( bytes, 14 )?;
+                let count = *bytes.get( 22 )? as usize;
+                let inputs = bytes.get( 23..23 + count * INPUT_SIZE )?.chunks( INPUT_SIZE )
+                    .map(|bytes| PlayerInput::from_bytes( bytes ))
+                    .collect();
+                let checksum = if checksum_tick > 0 { Some( ( checksum_tick, checksum ) ) } else { None };
+                Some( Packet::Inputs { ack, start, inputs, checksum } )
+            }
+            _ => None,
+        }
+    }
+}
+
+//one side of an online match: handshake, then trading inputs with the peer every tick
+pub struct NetSession
+{
+    link: Link,
+    role: NetRole,
+    rollback: Option<Rollback>,
+    waiting: Game,
+    remote_ack: usize,
+    last_hello: Option<Instant>,
+}
+
+impl NetSession
+{
+    //the host decides the match, only the player count is fixed for online play
+    pub fn host( port: u16, conditions: LinkConditions, mut config: MatchConfig ) -> io::Result<NetSession>
+    {
+        config.num_players = ONLINE_PLAYERS;
+        //the welcome goes out as a single datagram, an arena too big for it would never arrive whole
+        let welcome_size = Packet::Welcome( config.clone() ).encode().len();
+        if welcome_size > MAX_PACKET_SIZE
+        {
+            let message = format!( "the arena is too big to send, {} bytes of {} allowed", welcome_size, MAX_PACKET_SIZE );
+            return Err( io::Error::new( io::ErrorKind::InvalidInput, message ) );
+        }
+        let link = Link::bind( port, None, conditions )?;
+        Ok( NetSession
+        {
+            link,
+            role: NetRole::Host,
+            rollback: None,
+            waiting: Game::new( config ),
+            remote_ack: 0,
+            last_hello: None,
+        })
+    }
+
+    pub fn join( port: u16, remote: SocketAddr, conditions: LinkConditions ) -> io::Result<NetSession>
+    {
+        let link = Link::bind( port, Some( remote ), conditions )?;
+        Ok( NetSession
+        {
+            link,
+            role: NetRole::Join,
+            rollback: None,
+            waiting: Game::new( MatchConfig::new( 0, ONLINE_PLAYERS ) ),
+            remote_ack: 0,
+            last_hello: None,
+        })
+    }
+
+    pub fn get_local_index( &self ) -> usize
+    {
+        match self.role
+        {
+            NetRole::Host => HOST_INDEX,
+            NetRole::Join => JOIN_INDEX,
+        }
+    }
+
+    //called once per tick with what the local player is pressing, false if the input was not used
+    //because we are still connecting or too far ahead of the peer
+    pub fn update( &mut self, input: PlayerInput ) -> bool
+    {
+        self.receive();
+
+        let advanced = match self.rollback
+        {
+            Some( ref mut rollback ) =>
+            {
+                if rollback.can_advance()
+                {
+                    rollback.add_local_input( input );
+                    true
+                }
+                else
+                {
+                    false
+                }
+            }
+            None => false,
+        };
+        if self.rollback.is_some()
+        {
+            self.send_inputs();
+        }
+        else
+        {
+            self.send_hello();
+        }
+        self.link.flush();
+        advanced
+    }
+
+    //blocks until the host's welcome arrives, so a joiner knows the arena before opening its window
+    pub fn wait_for_welcome( &mut self )
+    {
+        while self.rollback.is_none()
+        {
+            self.receive();
+            self.send_hello();
+            self.link.flush();
+            thread::sleep( Duration::from_millis( WELCOME_POLL_MS ) );
+        }
+    }
+
+    fn receive( &mut self )
+    {
+        for bytes in self.link.poll()
+        {
+            if let Some( packet ) = Packet::decode( &bytes )
+            {
+                self.handle_packet( packet );
+            }
+        }
+    }
+
+    fn start( &mut self, config: MatchConfig )
+    {
+        if self.rollback.is_none()
+        {
+            let local_index = self.get_local_index();
+            self.rollback = Some( Rollback::new( config, local_index ) );
+        }
+    }
+
+    fn handle_packet( &mut self, packet: Packet )
+    {
+        match packet
+        {
+            Packet::Hello =>
+            {
+                if let NetRole::Host = self.role
+                {
+                    let config = self.waiting.get_config().clone();
+                    self.start( config.clone() );
+                    self.link.send( Packet::Welcome( config ).encode() );
+                }
+            }
+            Packet::Welcome( config ) =>
+            {
+                if let NetRole::Join = self.role
+                {
+                    self.start( config );
+                }
+            }
+            Packet::Inputs { ack, start, inputs, checksum } =>
+            {
+                if let Some( ref mut rollback ) = self.rollback
+                {
+                    self.remote_ack = self.remote_ack.max( ack );
+                    rollback.add_remote_inputs( start, &inputs );
+                    if let Some( ( tick, checksum ) ) = checksum
+                    {
+                        rollback.check_remote_checksum( tick, checksum );
+                    }
+                }
+            }
+        }
+    }
+
+    fn send_hello( &mut self )
+    {
+        if let NetRole::Join = self.role
+        {
+            let due = match self.last_hello
+            {
+                Some( last ) => last.elapsed() >= Duration::from_millis( HELLO_INTERVAL_MS ),
+                None => true,
+            };
+            if due
+            {
+                self.last_hello = Some( Instant::now() );
+                self.link.send( Packet::Hello.encode() );
+            }
+        }
+    }
+
+    //resends everything the peer has not acknowledged yet, so lost packets heal themselves
+    fn send_inputs( &mut self )
+    {
+        let packet = match self.rollback
+        {
+            Some( ref rollback ) =>
+            {
+                let local_inputs = rollback.get_local_inputs();
+                let start = self.remote_ack.min( local_inputs.len() );
+                let end = local_inputs.len().min( start + MAX_INPUTS_PER_PACKET );
+                Packet::Inputs
+                {
+                    ack: rollback.num_remote_inputs(),
+                    start,
+                    inputs: local_inputs[start..end].to_vec(),
+                    checksum: rollback.get_latest_checksum(),
+                }
+            }
+            None => return,
+        };
+        self.link.send( packet.encode() );
+    }
+
+    pub fn get_game( &self ) -> &Game
+    {
+        match self.rollback
+        {
+            Some( ref rollback ) => rollback.get_current(),
+            None => &self.waiting,
+        }
+    }
+
+    pub fn get_rollback_mut( &mut self ) -> Option<&mut Rollback>
+    {
+        self.rollback.as_mut()
+    }
+
+    pub fn is_connected( &self ) -> bool
+    {
+        self.rollback.is_some()
+    }
+
+    pub fn is_desynced( &self ) -> bool
+    {
+        self.rollback.as_ref().map_or( false, |rollback| rollback.is_desynced() )
+    }
+}
@@ -0,0 +1,156 @@
+use ncollide2d::query::Proximity;
+use ncollide2d::shape::{Ball, Cuboid, ShapeHandle};
+use ncollide2d::world::CollisionGroups;
+use nphysics2d::math::{Isometry, Vector, Velocity};
+use nphysics2d::object::{BodyHandle, ColliderHandle, Material, SensorHandle};
+use nphysics2d::volumetric::Volumetric;
+use nphysics2d::world::World;
+
+use player::*;
+use projectile::*;
+use tile::*;
+
+const ROBOT_DENSITY : f32 = 1.0;
+//tiles are heavier than robots for their size and bounce off, so a hit sends a robot further than
+//the tile was going
+const TILE_DENSITY : f32 = 2.0;
+const TILE_RESTITUTION : f32 = 1.0;
+const COLLIDER_MARGIN : f32 = 0.01;
+//robots are in the collision group of their index, so a tile can pick out the ones it may hit
+const TILE_GROUP : usize = 29;
+
+pub fn get_tile_mass() -> f32
+{
+    let half_size = TILE_SIZE as f32 / 2.0;
+    Cuboid::new( Vector::new( half_size, half_size ) ).mass( TILE_DENSITY )
+}
+
+//moves every robot standing on the floor one tick as a rigid body, so they shove each other
+//instead of walking through, and flies the thrown tiles through the same world so a tile pushes
+//whoever it hits with its own momentum. a tile only collides with the robots it can hit, and a
+//shield stops it before it pushes, so shielded robots merely sense it. the world is built afresh
+//every tick, which keeps Game a plain value that rollback can clone and plays out the same after a
+//rollback as without one. units are pixels and ticks. returns every robot and tile index pair that
+//touched, in order
+pub fn step_bodies( players: &mut [Player], projectiles: &[Projectile], can_hit: &dyn Fn( &Player, &Projectile ) -> bool ) -> Vec<( usize, usize )>
+{
+    let mut world = World::new();
+    world.set_timestep( 1.0 );
+    let ball = Ball::new( ROBOT_RADIUS );
+    let ball_shape = ShapeHandle::new( ball.clone() );
+    let half_size = TILE_SIZE as f32 / 2.0;
+    let cuboid = Cuboid::new( Vector::new( half_size, half_size ) );
+    let cuboid_shape = ShapeHandle::new( cuboid.clone() );
+
+    let robots : Vec<Option<( BodyHandle, ColliderHandle )>> = players.iter()
+        .enumerate()
+        .map(|( index, player )| {
+            if !player.is_on_floor()
+            {
+                return None;
+            }
+            let pos = Isometry::new( Vector::new( player.get_pos_x(), player.get_pos_y() ), 0.0 );
+            let handle = world.add_rigid_body( pos, ball.inertia( ROBOT_DENSITY ), ball.center_of_mass() );
+            let collider = world.add_collider( COLLIDER_MARGIN, ball_shape.clone(), handle, Isometry::identity(), Material::new( 0.0, 0.0 ) );
+            let mut groups = CollisionGroups::new();
+            groups.set_membership( &[index] );
+            world.collision_world_mut().set_collision_groups( collider, groups );
+            if let Some( body ) = world.rigid_body_mut( handle )
+            {
+                //a robot standing still still has to be pushed around
+                body.activation_status_mut().set_deactivation_threshold( None );
+                body.set_velocity( Velocity::linear( player.get_move_vel_x(), player.get_move_vel_y() ) );
+            }
+            Some( ( handle, collider ) )
+        })
+        .collect();
+
+    let tiles : Vec<Option<( ColliderHandle, SensorHandle )>> = projectiles.iter()
+        .map(|projectile| {
+            if projectile.is_dead()
+            {
+                return None;
+            }
+            let mut pushed = Vec::new();
+            let mut sensed = Vec::new();
+            for ( index, player ) in players.iter().enumerate()
+            {
+                if !player.is_on_floor() || player.is_invulnerable() || !can_hit( player, projectile )
+                {
+                    continue;
+                }
+                if player.get_boosts().has_shield()
+                {
+                    sensed.push( index );
+                }
+                else
+                {
+                    pushed.push( index );
+                }
+            }
+            let pos = Isometry::new( Vector::new( projectile.get_pos_x() + half_size, projectile.get_pos_y() + half_size ), projectile.get_angle() );
+            let density = projectile.get_mass() / cuboid.area();
+            let handle = world.add_rigid_body( pos, cuboid.inertia( density ), cuboid.center_of_mass() );
+            let collider = world.add_collider( COLLIDER_MARGIN, cuboid_shape.clone(), handle, Isometry::identity(), Material::new( TILE_RESTITUTION, 0.0 ) );
+            world.collision_world_mut().set_collision_groups( collider, get_tile_groups( &pushed ) );
+            let sensor = world.add_sensor( cuboid_shape.clone(), handle, Isometry::identity() );
+            world.collision_world_mut().set_collision_groups( sensor, get_tile_groups( &sensed ) );
+            if let Some( body ) = world.rigid_body_mut( handle )
+            {
+                body.activation_status_mut().set_deactivation_threshold( None );
+                body.set_velocity( Velocity::linear( projectile.get_vel_x(), projectile.get_vel_y() ) );
+            }
+            Some( ( collider, sensor ) )
+        })
+        .collect();
+
+    world.step();
+
+    for ( player, robot ) in players.iter_mut().zip( &robots )
+    {
+        if let Some( body ) = robot.and_then(|( handle, _ )| world.rigid_body( handle ))
+        {
+            let pos = body.position().translation.vector;
+            let vel = body.velocity().linear;
+            player.set_motion( pos.x, pos.y, vel.x, vel.y );
+        }
+    }
+
+    //the tiles themselves stay where they are, they break on whatever they hit and fly on by
+    //themselves otherwise. contacts are the ones the step resolved, at the positions it started from
+    let mut hits = Vec::new();
+    for ( first, second, contacts ) in world.collision_world().contact_pairs()
+    {
+        if contacts.num_contacts() > 0
+        {
+            hits.extend( find_hit( &robots, &tiles, first.handle(), second.handle() ) );
+        }
+    }
+    for ( first, second, proximity ) in world.collision_world().proximity_pairs()
+    {
+        if proximity.proximity() == Proximity::Intersecting
+        {
+            hits.extend( find_hit( &robots, &tiles, first.handle(), second.handle() ) );
+        }
+    }
+    hits.sort();
+    hits
+}
+
+//a tile collides with the given robot groups and nothing else, other tiles included
+fn get_tile_groups( robots: &[usize] ) -> CollisionGroups
+{
+    let mut groups = CollisionGroups::new();
+    groups.set_membership( &[TILE_GROUP] );
+    groups.set_whitelist( robots );
+    groups
+}
+
+//the robot and tile index behind a pair of colliders, if it is one of each
+fn find_hit( robots: &[Option<( BodyHandle, ColliderHandle )>], tiles: &[Option<( ColliderHandle, SensorHandle )>], first: ColliderHandle, second: ColliderHandle ) -> Option<( usize, usize )>
+{
+    let robot = |handle: ColliderHandle| robots.iter().position(|robot| robot.map(|( _, collider )| collider) == Some( handle ));
+    let tile = |handle: ColliderHandle| tiles.iter().position(|tile| tile.map_or( false, |( collider, sensor )| collider == handle || sensor == handle ));
+    robot( first ).and_then(|robot| tile( second ).map(|tile| ( robot, tile )))
+        .or_else(|| robot( second ).and_then(|robot| tile( first ).map(|tile| ( robot, tile ))))
+}
@@ -1,16 +1,36 @@
-use ggez::graphics;
-use ggez::Context;
-use ggez::GameResult;
-
-use std::f32::{self, consts};
-
 use tile::*;
 use projectile::*;
-use hitbox::*;
 use anim::*;
-
-const THROW_SPEED : f32 = 3.0;
-
+use game::*;
+use powerup::*;
+
+pub const THROW_SPEED : f32 = 3.0;
+pub const ROBOT_RADIUS : f32 = 16.0;
+//two keys are rarely let go on the same tick, a diagonal aim survives one of them for this long
+const AIM_GRACE_TICKS : u32 = 6;
+//how much the robot's speed changes per tick, towards where its driver steers it or to a stop
+const ACCELERATION : f32 = 0.5;
+const FRICTION : f32 = 0.5;
+const ICE_ACCELERATION : f32 = 0.05;
+const ICE_FRICTION : f32 = 0.02;
+const CONVEYOR_SPEED : f32 = 1.0;
+//heavy tiles are a pain to throw
+const HEAVY_THROW_SCALE : f32 = 0.6;
+//the second tile of a double throw goes this far off the aim, in radians
+const DOUBLE_THROW_SPREAD : f32 = 0.2;
+//a magnet reaches this many tiles out along the aim
+const MAGNET_REACH : usize = 3;
+//a robot knocked back by a tile can't steer until it recovers
+pub const KNOCKBACK_STUN_TICKS : u32 = 20;
+//an empty-handed action shoves a robot this close in front, sending it about a tile's width
+const SHOVE_REACH : f32 = 8.0;
+const SHOVE_SPEED : f32 = 6.0;
+const SHOVE_COOLDOWN_TICKS : u32 = 60;
+//after losing a hit point, or a life and coming back, tiles pass through the robot for a while
+const HIT_INVULNERABLE_TICKS : u32 = 60;
+const RESPAWN_INVULNERABLE_TICKS : u32 = 2 * TICKS_PER_SECOND;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Direction
 {
     UP,
@@ -19,150 +39,393 @@ pub enum Direction
     RIGHT,
 }
 
+impl Direction
+{
+    pub fn get_vec( &self ) -> ( f32, f32 )
+    {
+        match *self
+        {
+            Direction::UP => ( 0.0, -1.0 ),
+            Direction::DOWN => ( 0.0, 1.0 ),
+            Direction::LEFT => ( -1.0, 0.0 ),
+            Direction::RIGHT => ( 1.0, 0.0 ),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Player
 {
     id: u32,
     pos_x: f32,
     pos_y: f32,
-    width: u32,
-    height: u32,
+    prev_pos_x: f32,
+    prev_pos_y: f32,
+    //where the driver steers, the robot only gets there at ACCELERATION
     vel_x: f32,
     vel_y: f32,
-    dir: Direction,
-    sprite: graphics::Image,
-    shadow_sprite: graphics::Image,
+    //how fast the robot actually moves, pushing and knockback included
+    move_vel_x: f32,
+    move_vel_y: f32,
+    stun: u32,
+    shove_cooldown: u32,
+    boosts: Boosts,
+    //unit vector the robot faces and throws along, any angle
+    aim_x: f32,
+    aim_y: f32,
+    aim_grace: u32,
     tile: Option<Tile>,
     tile_image_id: usize,
-    hitbox: Hitbox,
     is_dead: bool,
     scale: f32,
     fall: bool,
-}
-
-fn player_debug_color() -> graphics::Color
-{
-    graphics::Color::new( 1.0, 0.0, 0.0, 1.0 )
-}
-
-fn tile_test_color() -> graphics::Color
-{
-    graphics::Color::new( 0.5, 0.5, 0.5, 1.0 )
+    //where the robot started the round, and comes back to after losing a life
+    spawn_x: f32,
+    spawn_y: f32,
+    spawn_dir: Direction,
+    max_hit_points: u32,
+    hit_points: u32,
+    lives: u32,
+    invulnerable: u32,
 }
 
 impl Player
 {
-    pub fn new( ctx: &mut Context, id: u32, pos_x: u32, pos_y: u32, init_dir: Direction ) -> Player
+    pub fn new( id: u32, pos_x: u32, pos_y: u32, init_dir: Direction ) -> Player
     {
-        let mut p = Player
+        let ( aim_x, aim_y ) = init_dir.get_vec();
+        Player
         { 
             id, 
             pos_x: pos_x as f32,
             pos_y: pos_y as f32,
-            width: 32,
-            height: 32,
+            prev_pos_x: pos_x as f32,
+            prev_pos_y: pos_y as f32,
             vel_x: 0.0,
             vel_y: 0.0,
-            dir: init_dir,
-            sprite: graphics::Image::new( ctx, "/robo.png" ).unwrap(),
-            shadow_sprite: graphics::Image::new( ctx, "/robo_shadow.png").unwrap(),
+            move_vel_x: 0.0,
+            move_vel_y: 0.0,
+            stun: 0,
+            shove_cooldown: 0,
+            boosts: Boosts::default(),
+            aim_x,
+            aim_y,
+            aim_grace: 0,
             tile: None,
             tile_image_id: 1,
-            hitbox: Hitbox::new( pos_x as f32, pos_y as f32, 32.0, 32.0 ),
             is_dead: false,
             scale: 1.0,
             fall: false,
-        };
-        if id == 1 
-        { 
-            p.sprite = graphics::Image::new( ctx, "/robo_red.png" ).unwrap();
+            spawn_x: pos_x as f32,
+            spawn_y: pos_y as f32,
+            spawn_dir: init_dir,
+            max_hit_points: 1,
+            hit_points: 1,
+            lives: 1,
+            invulnerable: 0,
         }
-        p
     }
 
-    fn get_adj_vel_x( &mut self ) -> f32
+    //a single hit point and life is the classic one hit and out
+    pub fn set_health( &mut self, hit_points: u32, lives: u32 )
     {
-        if self.vel_x != 0.0 && self.vel_y != 0.0
+        self.max_hit_points = hit_points.max( 1 );
+        self.hit_points = self.max_hit_points;
+        self.lives = lives.max( 1 );
+    }
+
+    //diagonals are no faster than straight lines, whether they come from two keys or a stick
+    fn get_adj_scale( &self ) -> f32
+    {
+        let speed = ( self.vel_x * self.vel_x + self.vel_y * self.vel_y ).sqrt();
+        let scale = if speed > PLAYER_SPEED
         {
-            self.vel_x * 0.7
+            PLAYER_SPEED / speed
         }
         else
         {
-            self.vel_x
-        }
+            1.0
+        };
+        scale * self.boosts.get_speed_scale()
+    }
+
+    fn get_adj_vel_x( &self ) -> f32
+    {
+        self.vel_x * self.get_adj_scale()
     }
 
-    fn get_adj_vel_y( &mut self ) -> f32
+    fn get_adj_vel_y( &self ) -> f32
     {
-        if self.vel_x != 0.0 && self.vel_y != 0.0
+        self.vel_y * self.get_adj_scale()
+    }
+
+    //first part of a tick, before the physics step moves the robots standing on the floor.
+    //a conveyor adds its own speed to wherever the driver steers
+    pub fn prepare_move( &mut self, tile_map: &TileMap )
+    {
+        if self.is_dead
+        {
+            return;
+        }
+        self.prev_pos_x = self.pos_x;
+        self.prev_pos_y = self.pos_y;
+
+        let ( mut target_x, mut target_y ) = if self.stun > 0
         {
-            self.vel_y * 0.7
+            ( 0.0, 0.0 )
         }
         else
         {
-            self.vel_y
+            ( self.get_adj_vel_x(), self.get_adj_vel_y() )
+        };
+        let driving = target_x != 0.0 || target_y != 0.0;
+        let floor = if self.fall { None } else { tile_map.get_floor_kind_at( self.pos_x, self.pos_y ) };
+        if let Some( TileKind::Conveyor( dir ) ) = floor
+        {
+            let ( dir_x, dir_y ) = dir.get_vec();
+            target_x += dir_x * CONVEYOR_SPEED;
+            target_y += dir_y * CONVEYOR_SPEED;
+        }
+        let rate = match ( floor, driving )
+        {
+            ( Some( TileKind::Ice ), true ) => ICE_ACCELERATION,
+            ( Some( TileKind::Ice ), false ) => ICE_FRICTION,
+            ( _, true ) => ACCELERATION,
+            ( _, false ) => FRICTION,
+        };
+        let diff_x = target_x - self.move_vel_x;
+        let diff_y = target_y - self.move_vel_y;
+        let diff = ( diff_x * diff_x + diff_y * diff_y ).sqrt();
+        if diff <= rate
+        {
+            self.move_vel_x = target_x;
+            self.move_vel_y = target_y;
+        }
+        else
+        {
+            self.move_vel_x += diff_x / diff * rate;
+            self.move_vel_y += diff_y / diff * rate;
         }
     }
 
-    fn change_pos_from_vel( &mut self, factor: f32 )
-    {
-        self.pos_x += self.get_adj_vel_x() * factor;
-        self.pos_y += self.get_adj_vel_y() * factor;
-    }
-
-    pub fn update( &mut self, ctx: &mut Context, projectiles: &mut Vec<Projectile>, anims: &mut Vec<Anim>, tile_map: &TileMap, factor: f32)
+    //robots falling off the edge are out of the physics step and drift on by themselves
+    pub fn update( &mut self, tile_map: &TileMap )
     {
         if self.is_dead
         {
             return;
         }
+        if self.stun > 0
+        {
+            self.stun -= 1;
+        }
+        if self.shove_cooldown > 0
+        {
+            self.shove_cooldown -= 1;
+        }
+        if self.invulnerable > 0
+        {
+            self.invulnerable -= 1;
+        }
+        self.boosts.update();
+        if self.aim_grace > 0
+        {
+            self.aim_grace -= 1;
+            if self.aim_grace == 0
+            {
+                self.aim_at_vel();
+            }
+        }
 
         if self.fall
         {
             self.scale -= 0.05;
+            self.pos_x += self.move_vel_x;
+            self.pos_y += self.move_vel_y;
             if self.scale < 0.01
             {
                 self.scale = 0.01;
-                self.is_dead = true;
+                self.lose_life( tile_map );
+                return;
             }
         }
-        self.change_pos_from_vel(factor);
-        self.hitbox.top_x = self.pos_x - self.width as f32 / 2.0;
-        self.hitbox.top_y = self.pos_y - self.height as f32 / 2.0;
 
         //check if we are standing on top of a tile
         //let center = self.get_center();
-        let tile_distance : usize = TILE_SPACE;
-        let tile_index_x : usize = self.pos_x as usize / tile_distance;
-        let tile_index_y : usize = self.pos_y as usize / tile_distance;
-
-        let tile = &tile_map.map[tile_index_y][tile_index_x];
-        match tile.get_state()
+        //off the edge of the map counts as empty too
+        if !tile_map.is_floor_at( self.pos_x, self.pos_y )
         {
-            TileState::EMPTY => {
-                //self.is_dead = true;
+            if !self.fall && !self.boosts.hover_over_hole()
+            {
                 self.fall = true;
             }
-            _ => {}
         }
+        else
+        {
+            self.boosts.land();
+        }
+    }
 
-        for projectile in projectiles
+    //hit by someone else's tile, which has already pushed the robot in the physics step. a robot
+    //with hit points to spare reels for a moment, the last one knocks it out
+    pub fn on_hit( &mut self, tile_map: &TileMap, anims: &mut Vec<Anim> )
+    {
+        self.hit_points = self.hit_points.saturating_sub( 1 );
+        if self.hit_points > 0
+        {
+            self.stun( KNOCKBACK_STUN_TICKS );
+            self.invulnerable = HIT_INVULNERABLE_TICKS;
+            return;
+        }
+        let prefix = "/collision/collision_animation_";
+        anims.push( Anim::new( self.pos_x - 32.0, self.pos_y - 32.0, prefix.to_string(), 4, 10 ) );
+        self.lose_life( tile_map );
+    }
+
+    //out for good on the last life, otherwise back at the spawn point
+    fn lose_life( &mut self, tile_map: &TileMap )
+    {
+        self.lives = self.lives.saturating_sub( 1 );
+        if self.lives == 0
+        {
+            self.is_dead = true;
+            return;
+        }
+        self.respawn( tile_map );
+    }
+
+    //the spawn point may have crumbled away by now, then the closest safe floor does.
+    //whatever was held or collected is lost, the keys still held keep steering
+    fn respawn( &mut self, tile_map: &TileMap )
+    {
+        let spot = if tile_map.is_safe_at( self.spawn_x, self.spawn_y )
+        {
+            Some( ( self.spawn_x, self.spawn_y ) )
+        }
+        else
         {
-            if self.id != projectile.get_owner() && self.hitbox.check_collision( projectile.get_hitbox() )
+            tile_map.find_safe_floor_near( self.spawn_x, self.spawn_y )
+        };
+        let ( pos_x, pos_y ) = match spot
+        {
+            Some( spot ) => spot,
+            None =>
             {
-                projectile.kill();
                 self.is_dead = true;
-                let prefix = "/collision/collision_animation_";
-                anims.push( Anim::new(ctx, self.pos_x - 32.0, self.pos_y - 32.0, prefix.to_string(), 4, 10 ) );
+                return;
             }
+        };
+        self.pos_x = pos_x;
+        self.pos_y = pos_y;
+        self.prev_pos_x = pos_x;
+        self.prev_pos_y = pos_y;
+        self.move_vel_x = 0.0;
+        self.move_vel_y = 0.0;
+        let ( aim_x, aim_y ) = self.spawn_dir.get_vec();
+        self.aim_x = aim_x;
+        self.aim_y = aim_y;
+        self.stun = 0;
+        self.boosts = Boosts::default();
+        self.tile = None;
+        self.scale = 1.0;
+        self.fall = false;
+        self.hit_points = self.max_hit_points;
+        self.invulnerable = RESPAWN_INVULNERABLE_TICKS;
+    }
+
+    pub fn is_invulnerable( &self ) -> bool
+    {
+        self.invulnerable > 0
+    }
+
+    pub fn get_invulnerable_ticks( &self ) -> u32
+    {
+        self.invulnerable
+    }
+
+    pub fn get_hit_points( &self ) -> u32
+    {
+        self.hit_points
+    }
+
+    pub fn get_lives( &self ) -> u32
+    {
+        self.lives
+    }
+
+    //pushed by something outside the physics step, a shove or a falling sky tile
+    pub fn knock_back( &mut self, vel_x: f32, vel_y: f32 )
+    {
+        self.move_vel_x += vel_x;
+        self.move_vel_y += vel_y;
+        self.stun( KNOCKBACK_STUN_TICKS );
+    }
+
+    //can't steer for at least the given number of ticks
+    pub fn stun( &mut self, ticks: u32 )
+    {
+        self.stun = self.stun.max( ticks );
+    }
+
+    //whether an action would shove other instead of picking up a tile: empty-handed, other standing
+    //right next to us and within 45 degrees of the aim
+    pub fn is_in_shove_reach( &self, other: &Player ) -> bool
+    {
+        if self.is_dead || self.tile.is_some() || !other.is_on_floor() || other.id == self.id
+        {
+            return false;
         }
+        let dist_x = other.pos_x - self.pos_x;
+        let dist_y = other.pos_y - self.pos_y;
+        let dist = ( dist_x * dist_x + dist_y * dist_y ).sqrt();
+        dist > 0.0 && dist <= ROBOT_RADIUS * 2.0 + SHOVE_REACH
+            && ( dist_x * self.aim_x + dist_y * self.aim_y ) / dist >= 0.5f32.sqrt()
     }
 
-    fn get_center( &self ) -> graphics::Point2
+    //does nothing while the last shove is still cooling down
+    pub fn shove( &mut self, other: &mut Player )
     {
-        graphics::Point2::new( 
-            self.pos_x + self.width as f32 / 2.0, 
-            self.pos_y + self.height as f32 / 2.0,
-            )
+        if self.shove_cooldown > 0
+        {
+            return;
+        }
+        self.shove_cooldown = SHOVE_COOLDOWN_TICKS;
+        other.knock_back( self.aim_x * SHOVE_SPEED, self.aim_y * SHOVE_SPEED );
+    }
+
+    pub fn get_shove_cooldown( &self ) -> u32
+    {
+        self.shove_cooldown
+    }
+
+    //where the physics step left the robot
+    pub fn set_motion( &mut self, pos_x: f32, pos_y: f32, move_vel_x: f32, move_vel_y: f32 )
+    {
+        self.pos_x = pos_x;
+        self.pos_y = pos_y;
+        self.move_vel_x = move_vel_x;
+        self.move_vel_y = move_vel_y;
+    }
+
+    pub fn is_on_floor( &self ) -> bool
+    {
+        !self.is_dead && !self.fall
+    }
+
+    pub fn add_boost( &mut self, kind: PowerupKind )
+    {
+        self.boosts.add( kind );
+    }
+
+    pub fn get_boosts( &self ) -> &Boosts
+    {
+        &self.boosts
+    }
+
+    //a shield soaks up the hit, true when it did
+    pub fn absorb_hit( &mut self ) -> bool
+    {
+        self.boosts.absorb_hit()
     }
 
     pub fn get_id( &self ) -> u32
@@ -175,93 +438,124 @@ impl Player
         self.is_dead
     }
 
-    pub fn draw( &mut self, ctx: &mut Context ) -> GameResult<()>
-    {       
-        if self.is_dead
+    pub fn get_pos_x( &self ) -> f32
+    {
+        self.pos_x
+    }
+
+    pub fn get_pos_y( &self ) -> f32
+    {
+        self.pos_y
+    }
+
+    pub fn get_prev_pos_x( &self ) -> f32
+    {
+        self.prev_pos_x
+    }
+
+    pub fn get_prev_pos_y( &self ) -> f32
+    {
+        self.prev_pos_y
+    }
+
+    pub fn get_vel_x( &self ) -> f32
+    {
+        self.vel_x
+    }
+
+    pub fn get_vel_y( &self ) -> f32
+    {
+        self.vel_y
+    }
+
+    pub fn get_move_vel_x( &self ) -> f32
+    {
+        self.move_vel_x
+    }
+
+    pub fn get_move_vel_y( &self ) -> f32
+    {
+        self.move_vel_y
+    }
+
+    pub fn is_stunned( &self ) -> bool
+    {
+        self.stun > 0
+    }
+
+    //the cardinal direction closest to the aim
+    pub fn get_dir( &self ) -> Direction
+    {
+        if self.aim_x.abs() > self.aim_y.abs()
+        {
+            if self.aim_x < 0.0 { Direction::LEFT } else { Direction::RIGHT }
+        }
+        else
         {
-            return Ok(());
+            if self.aim_y < 0.0 { Direction::UP } else { Direction::DOWN }
         }
+    }
 
-        //draw player shadow
-        let top_right = graphics::Point2::new(self.pos_x + 5.0, self.pos_y + 2.0);
-        let param = graphics::DrawParam {
-            dest: top_right,
-            rotation: self.get_facing_radians(),
-            offset: graphics::Point2::new(0.5, 0.5),
-            scale: graphics::Point2::new( self.scale, self.scale ),
-            ..Default::default()
-        };
-        graphics::draw_ex(ctx, &self.shadow_sprite, param );
-        //draw player   
-        let top_right = graphics::Point2::new(self.pos_x, self.pos_y );
-        let param = graphics::DrawParam {
-            dest: top_right,
-            rotation: self.get_facing_radians(),
-            offset: graphics::Point2::new(0.5, 0.5),
-            scale: graphics::Point2::new( self.scale, self.scale ),
-            ..Default::default()
-        };
-        graphics::draw_ex(ctx, &self.sprite, param );
-
-        /*let test_sprite = graphics::Image::solid( ctx, 32, player_debug_color() ).unwrap();
-        let test_pos = graphics::Point2::new(self.pos_x - self.width as f32 / 2.0, self.pos_y - self.height as f32 / 2.0);
-        graphics::draw(ctx, &test_sprite, test_pos, 0.0 );
-        */
-
-        let tile_draw_pos : graphics::Point2 = graphics::Point2::new
-        (
-            self.pos_x + self.get_tile_offset_x(),
-            self.pos_y + self.get_tile_offset_y()
-        );
-        let shadow_draw_pos : graphics::Point2 = graphics::Point2::new
-        (
-            self.pos_x + self.get_tile_offset_x() + 2.0,
-            self.pos_y + self.get_tile_offset_y() + 5.0
-        );
-        match self.tile
-        {
-            Some( ref mut tile ) =>
-            {
-                //draw the tile shadow
-                let color = graphics::Color::new( 0.0, 0.0, 0.0, 0.7);
-                let shadow = graphics::Image::solid( ctx, 32, color).unwrap();
-                graphics::draw(ctx, &shadow, shadow_draw_pos, 0.0);
-                //then draw the tile
-                tile.draw_at_pos(ctx, &tile_draw_pos );
-            }
-            None => {}
+    //exactly along dir, not just closest to it
+    pub fn is_facing( &self, dir: Direction ) -> bool
+    {
+        ( self.aim_x, self.aim_y ) == dir.get_vec()
+    }
+
+    pub fn get_aim_x( &self ) -> f32
+    {
+        self.aim_x
+    }
+
+    pub fn get_aim_y( &self ) -> f32
+    {
+        self.aim_y
+    }
+
+    pub fn get_scale( &self ) -> f32
+    {
+        self.scale
+    }
+
+    pub fn get_held_tile( &self ) -> Option<&Tile>
+    {
+        self.tile.as_ref()
+    }
+
+    //hands over the held tile to go somewhere other than a throw
+    pub fn take_held_tile( &mut self ) -> Option<Tile>
+    {
+        self.tile.take()
+    }
+
+    //faces wherever the robot is heading, standing still keeps the last aim
+    fn aim_at_vel( &mut self )
+    {
+        let speed = ( self.vel_x * self.vel_x + self.vel_y * self.vel_y ).sqrt();
+        if speed > 0.0
+        {
+            self.aim_x = self.vel_x / speed;
+            self.aim_y = self.vel_y / speed;
         }
-        Ok(())
     }
 
     pub fn set_vel_x( &mut self, vel_x: f32 )
     {
         self.vel_x = vel_x;
-        if self.vel_x > 0.0 
-        {
-            self.dir = Direction::RIGHT;
-        }
-        else if self.vel_x < 0.0
-        {
-            self.dir = Direction::LEFT;
-        }
+        self.aim_grace = 0;
+        self.aim_at_vel();
     }
 
     pub fn set_vel_y( &mut self, vel_y: f32 )
     {
         self.vel_y = vel_y;
-        if self.vel_y > 0.0
-        {
-            self.dir = Direction::DOWN;
-        }
-        else if self.vel_y < 0.0
-        {
-            self.dir = Direction::UP;
-        }
+        self.aim_grace = 0;
+        self.aim_at_vel();
     }
 
     pub fn on_dir_released( &mut self, dir: Direction )
     {
+        let diagonal = self.vel_x != 0.0 && self.vel_y != 0.0;
         match dir 
         {
             Direction::UP => 
@@ -293,45 +587,45 @@ impl Player
                 } 
             }         
         }
+        if diagonal && ( self.vel_x != 0.0 || self.vel_y != 0.0 )
+        {
+            //wait and see if the other key follows before turning
+            self.aim_grace = AIM_GRACE_TICKS;
+        }
+        else if self.aim_grace == 0
+        {
+            self.aim_at_vel();
+        }
+        else if self.vel_x == 0.0 && self.vel_y == 0.0
+        {
+            //it did, the diagonal stays
+            self.aim_grace = 0;
+        }
     }
 
-    fn get_facing_radians( &self ) -> f32
+    pub fn get_facing_radians( &self ) -> f32
     {
         self.get_facing_degrees().to_radians()
     }
 
+    //the sprite faces down unrotated, rotations go clockwise
     fn get_facing_degrees( &self ) -> f32
     {
-        match self.dir
-        {
-            Direction::UP => { 180.0 }
-            Direction::DOWN => { 0.0 }
-            Direction::LEFT => { 90.0 }
-            Direction::RIGHT => { 270.0 }
-        }
+        ( -self.aim_x ).atan2( self.aim_y ).to_degrees()
     }
 
-    fn get_tile_offset_x( &self ) -> f32
+    //the held tile sits a tile's width out along the aim
+    pub fn get_tile_offset_x( &self ) -> f32
     {
-        match self.dir
-        {
-            Direction::LEFT => { -48.0 }
-            Direction::RIGHT => { 16.0 }
-            _ => { -16.0 }
-        }
+        self.aim_x * 32.0 - 16.0
     }
 
-    fn get_tile_offset_y( &self ) -> f32
+    pub fn get_tile_offset_y( &self ) -> f32
     {
-        match self.dir
-        {
-            Direction::UP => { -48.0 }
-            Direction::DOWN => { 16.0 }
-            _ => { -16.0 }
-        }
+        self.aim_y * 32.0 - 16.0
     }
 
-    pub fn on_action( &mut self, ctx: &mut Context, tile_map: &mut TileMap, projectiles: &mut Vec<Projectile> )
+    pub fn on_action( &mut self, tile_map: &mut TileMap, projectiles: &mut Vec<Projectile> )
     {
         if self.is_dead
         {
@@ -339,72 +633,73 @@ impl Player
         }
         if self.tile.is_none()
         {
-            self.pickup_tile(ctx, tile_map);
+            self.pickup_tile( tile_map );
         }
         else
         {
-            self.throw_tile(ctx, projectiles);
+            self.throw_tile( projectiles );
         }
     }
 
-    fn throw_tile( &mut self, ctx: &mut Context, projectiles: &mut Vec<Projectile> )
-    {
-        self.tile = None;
-        projectiles.push( Projectile::new( 
-            ctx, 
-            self.id, 
-            self.pos_x + self.get_tile_offset_x(), 
-            self.pos_y + self.get_tile_offset_y(), 
-            self.get_adj_vel_x() + self.get_throw_vel_x(),
-            self.get_adj_vel_y() + self.get_throw_vel_y(),
-            self.tile_image_id ));
-    }
-
-    fn get_throw_vel_x( &self ) -> f32
+    fn throw_tile( &mut self, projectiles: &mut Vec<Projectile> )
     {
-        match self.dir
+        let kind = match self.tile.take()
         {
-            Direction::LEFT => { -THROW_SPEED }
-            Direction::RIGHT => { THROW_SPEED }
-            _ => { 0.0 }
-        }
-    }
-
-    fn get_throw_vel_y( &self ) -> f32
-    {
-        match self.dir
+            Some( tile ) => tile.kind,
+            None => return,
+        };
+        let speed = if kind == TileKind::Heavy { THROW_SPEED * HEAVY_THROW_SCALE } else { THROW_SPEED };
+        let spreads : &[f32] = if self.boosts.has_double_throw() { &[-DOUBLE_THROW_SPREAD, DOUBLE_THROW_SPREAD] } else { &[0.0] };
+        for spread in spreads
         {
-            Direction::UP => { -THROW_SPEED }
-            Direction::DOWN => { THROW_SPEED }
-            _ => { 0.0 }
+            let ( sin, cos ) = spread.sin_cos();
+            let dir_x = self.aim_x * cos - self.aim_y * sin;
+            let dir_y = self.aim_x * sin + self.aim_y * cos;
+            projectiles.push( Projectile::new( 
+                self.id, 
+                self.pos_x + self.get_tile_offset_x(), 
+                self.pos_y + self.get_tile_offset_y(), 
+                self.move_vel_x + dir_x * speed,
+                self.move_vel_y + dir_y * speed,
+                self.tile_image_id,
+                kind ));
         }
     }
 
-    pub fn pickup_tile( &mut self, ctx: &mut Context, tile_map: &mut TileMap )
+    pub fn pickup_tile( &mut self, tile_map: &mut TileMap )
     {
         if self.tile.is_some() 
         {
             return;
         }
 
-        let tile_distance : usize = TILE_SPACE;
-        let mut tile_index_x : usize = self.pos_x as usize / tile_distance;
-        let mut tile_index_y : usize = self.pos_y as usize / tile_distance;
-        match self.dir
+        //stretched out to the ring of tiles around us, so a diagonal aim reaches the corner tile
+        let tile_distance = TILE_SPACE as f32 / self.aim_x.abs().max( self.aim_y.abs() );
+        let reach = if self.boosts.has_magnet() { MAGNET_REACH } else { 1 };
+        //a magnet pulls in the nearest tile that can be picked up
+        let ring = ( 1..reach + 1 )
+            .find(|ring| {
+                let distance = tile_distance * *ring as f32;
+                tile_map.can_pick_up_at( self.pos_x + self.aim_x * distance, self.pos_y + self.aim_y * distance )
+            })
+            .unwrap_or( 1 );
+        let tile_pos_x = self.pos_x + self.aim_x * tile_distance * ring as f32;
+        let tile_pos_y = self.pos_y + self.aim_y * tile_distance * ring as f32;
+        let tile = match tile_map.get_tile_at_mut( tile_pos_x, tile_pos_y )
         {
-            Direction::UP => { tile_index_y -= 1; }
-            Direction::DOWN => { tile_index_y += 1; }
-            Direction::LEFT => { tile_index_x -= 1; }
-            Direction::RIGHT => { tile_index_x += 1; }
-        }       
-        let tile = &mut tile_map.map[tile_index_y][tile_index_x];
+            Some( tile ) => tile,
+            None => return,
+        };
         match &tile.get_state()
         {
-            TileState::FULL =>
+            TileState::FULL if tile.kind.can_pick_up() =>
             {
                 tile.change_state( TileState::EMPTY );
                 self.tile_image_id = tile.image_id;
-                self.tile = Some( Tile::new( ctx, 0, 0, self.tile_image_id ) );
+                //the held tile remembers the cell it came from
+                let mut held = Tile::new( tile.get_pos_x() / TILE_SPACE, tile.get_pos_y() / TILE_SPACE, self.tile_image_id );
+                held.kind = tile.kind;
+                self.tile = Some( held );
             }
             _ => {}
         }
@@ -0,0 +1,323 @@
+use game::TICKS_PER_SECOND;
+use tile::*;
+use rng::*;
+
+//a power-up nobody collects disappears again after this long
+const POWERUP_LIFETIME_TICKS : u32 = 10 * TICKS_PER_SECOND;
+const SPEED_TICKS : u32 = 8 * TICKS_PER_SECOND;
+const DOUBLE_THROW_TICKS : u32 = 10 * TICKS_PER_SECOND;
+const MAGNET_TICKS : u32 = 10 * TICKS_PER_SECOND;
+//how long a hover keeps a robot up over a hole before it drops after all
+const HOVER_TICKS : u32 = 90;
+const SPEED_SCALE : f32 = 1.5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerupKind
+{
+    //a faster top speed
+    Speed,
+    //every throw sends out a second tile alongside the first
+    DoubleThrow,
+    //the next tile that hits does nothing
+    Shield,
+    //tiles further along the aim can be picked up
+    Magnet,
+    //the next hole is crossed instead of fallen into
+    Hover,
+}
+
+pub const POWERUP_KINDS : [PowerupKind; 5] = [
+    PowerupKind::Speed,
+    PowerupKind::DoubleThrow,
+    PowerupKind::Shield,
+    PowerupKind::Magnet,
+    PowerupKind::Hover,
+];
+
+impl PowerupKind
+{
+    pub fn get_name( &self ) -> &'static str
+    {
+        match *self
+        {
+            PowerupKind::Speed => "speed",
+            PowerupKind::DoubleThrow => "double",
+            PowerupKind::Shield => "shield",
+            PowerupKind::Magnet => "magnet",
+            PowerupKind::Hover => "hover",
+        }
+    }
+
+    pub fn from_name( name: &str ) -> Option<PowerupKind>
+    {
+        POWERUP_KINDS.iter().cloned().find(|kind| kind.get_name() == name)
+    }
+
+    //position in POWERUP_KINDS, used for the pool bits in replays and packets
+    pub fn get_index( &self ) -> usize
+    {
+        POWERUP_KINDS.iter().position(|kind| kind == self).unwrap_or( 0 )
+    }
+}
+
+//how often power-ups turn up in a match and which ones
+#[derive(Clone, PartialEq, Debug)]
+pub struct PowerupConfig
+{
+    //a new power-up appears this often, 0 turns them off
+    pub interval_ticks: u32,
+    //no new ones appear while this many are lying around
+    pub max_on_floor: usize,
+    //what can appear, picked from evenly
+    pub pool: Vec<PowerupKind>,
+}
+
+impl Default for PowerupConfig
+{
+    fn default() -> PowerupConfig
+    {
+        PowerupConfig
+        {
+            interval_ticks: 0,
+            max_on_floor: 2,
+            pool: POWERUP_KINDS.to_vec(),
+        }
+    }
+}
+
+impl PowerupConfig
+{
+    pub fn is_enabled( &self ) -> bool
+    {
+        self.interval_ticks > 0 && !self.pool.is_empty()
+    }
+
+    //one bit per kind, in POWERUP_KINDS order
+    pub fn get_pool_bits( &self ) -> u8
+    {
+        self.pool.iter().fold( 0, |bits, kind| bits | 1 << kind.get_index() )
+    }
+
+    pub fn set_pool_bits( &mut self, bits: u8 )
+    {
+        self.pool = POWERUP_KINDS.iter().cloned().filter(|kind| bits & 1 << kind.get_index() != 0).collect();
+    }
+}
+
+//a power-up lying on a floor cell, collected by the first robot to walk over it
+#[derive(Clone)]
+pub struct Powerup
+{
+    pub kind: PowerupKind,
+    pub index_x: usize,
+    pub index_y: usize,
+    ticks_left: u32,
+}
+
+impl Powerup
+{
+    pub fn get_pos_x( &self ) -> f32
+    {
+        ( self.index_x * TILE_SPACE + TILE_SIZE / 2 ) as f32
+    }
+
+    pub fn get_pos_y( &self ) -> f32
+    {
+        ( self.index_y * TILE_SPACE + TILE_SIZE / 2 ) as f32
+    }
+
+    pub fn get_ticks_left( &self ) -> u32
+    {
+        self.ticks_left
+    }
+
+    pub fn is_at( &self, pos_x: f32, pos_y: f32 ) -> bool
+    {
+        pos_x >= 0.0 && pos_y >= 0.0
+            && pos_x as usize / TILE_SPACE == self.index_x && pos_y as usize / TILE_SPACE == self.index_y
+    }
+}
+
+//puts power-ups on random floor tiles as the match goes on. it draws from the power-up stream of
+//the match, so the same seed always brings the same power-ups to the same places
+#[derive(Clone)]
+pub struct PowerupSpawner
+{
+    config: PowerupConfig,
+    ticks_to_next: u32,
+    pub powerups: Vec<Powerup>,
+}
+
+impl PowerupSpawner
+{
+    pub fn new( config: PowerupConfig ) -> PowerupSpawner
+    {
+        PowerupSpawner
+        {
+            ticks_to_next: config.interval_ticks,
+            config,
+            powerups: Vec::new(),
+        }
+    }
+
+    //ages the power-ups lying around and drops a new one when it is time. occupied holds the
+    //positions of the robots, nothing appears right under one
+    pub fn update( &mut self, tile_map: &TileMap, occupied: &[( f32, f32 )], rng: &mut Rng )
+    {
+        for powerup in &mut self.powerups
+        {
+            powerup.ticks_left = powerup.ticks_left.saturating_sub( 1 );
+        }
+        //gone with its tile, or simply not collected in time
+        self.powerups.retain(|powerup| {
+            let tile = &tile_map.map[powerup.index_y][powerup.index_x];
+            powerup.ticks_left > 0 && *tile.get_state() == TileState::FULL && !tile.is_crumbling()
+        });
+
+        if !self.config.is_enabled()
+        {
+            return;
+        }
+        self.ticks_to_next = self.ticks_to_next.saturating_sub( 1 );
+        if self.ticks_to_next > 0
+        {
+            return;
+        }
+        self.ticks_to_next = self.config.interval_ticks;
+        if self.powerups.len() >= self.config.max_on_floor
+        {
+            return;
+        }
+
+        let mut cells = Vec::new();
+        for ( y_index, row ) in tile_map.map.iter().enumerate()
+        {
+            for ( x_index, tile ) in row.iter().enumerate()
+            {
+                let free = *tile.get_state() == TileState::FULL && !tile.is_crumbling()
+                    && !self.powerups.iter().any(|powerup| powerup.index_x == x_index && powerup.index_y == y_index)
+                    && !occupied.iter().any(|&( pos_x, pos_y )| pos_x as usize / TILE_SPACE == x_index && pos_y as usize / TILE_SPACE == y_index);
+                if free
+                {
+                    cells.push( ( x_index, y_index ) );
+                }
+            }
+        }
+        if cells.is_empty()
+        {
+            return;
+        }
+        let ( index_x, index_y ) = cells[rng.next_below( cells.len() )];
+        let kind = self.config.pool[rng.next_below( self.config.pool.len() )];
+        self.powerups.push( Powerup { kind, index_x, index_y, ticks_left: POWERUP_LIFETIME_TICKS } );
+    }
+
+    //takes away the power-up under a point, if there is one
+    pub fn collect_at( &mut self, pos_x: f32, pos_y: f32 ) -> Option<PowerupKind>
+    {
+        let index = self.powerups.iter().position(|powerup| powerup.is_at( pos_x, pos_y ))?;
+        Some( self.powerups.remove( index ).kind )
+    }
+}
+
+//what a robot has collected and how long each of it lasts, in ticks
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Boosts
+{
+    speed_ticks: u32,
+    double_throw_ticks: u32,
+    magnet_ticks: u32,
+    shield: bool,
+    hover: bool,
+    //ticks left floating over a hole, 0 when not hovering
+    hovering: u32,
+}
+
+impl Boosts
+{
+    pub fn add( &mut self, kind: PowerupKind )
+    {
+        match kind
+        {
+            PowerupKind::Speed => { self.speed_ticks = SPEED_TICKS }
+            PowerupKind::DoubleThrow => { self.double_throw_ticks = DOUBLE_THROW_TICKS }
+            PowerupKind::Shield => { self.shield = true }
+            PowerupKind::Magnet => { self.magnet_ticks = MAGNET_TICKS }
+            PowerupKind::Hover => { self.hover = true }
+        }
+    }
+
+    pub fn update( &mut self )
+    {
+        self.speed_ticks = self.speed_ticks.saturating_sub( 1 );
+        self.double_throw_ticks = self.double_throw_ticks.saturating_sub( 1 );
+        self.magnet_ticks = self.magnet_ticks.saturating_sub( 1 );
+    }
+
+    pub fn get_speed_scale( &self ) -> f32
+    {
+        if self.speed_ticks > 0 { SPEED_SCALE } else { 1.0 }
+    }
+
+    pub fn has_double_throw( &self ) -> bool
+    {
+        self.double_throw_ticks > 0
+    }
+
+    pub fn has_magnet( &self ) -> bool
+    {
+        self.magnet_ticks > 0
+    }
+
+    pub fn has_shield( &self ) -> bool
+    {
+        self.shield
+    }
+
+    //uses up the shield if there is one, true when it took the hit
+    pub fn absorb_hit( &mut self ) -> bool
+    {
+        let shield = self.shield;
+        self.shield = false;
+        shield
+    }
+
+    pub fn has_hover( &self ) -> bool
+    {
+        self.hover || self.hovering > 0
+    }
+
+    pub fn is_hovering( &self ) -> bool
+    {
+        self.hovering > 0
+    }
+
+    //called every tick the robot is over a hole, true while it stays up
+    pub fn hover_over_hole( &mut self ) -> bool
+    {
+        if self.hover
+        {
+            self.hover = false;
+            self.hovering = HOVER_TICKS;
+        }
+        if self.hovering == 0
+        {
+            return false;
+        }
+        self.hovering -= 1;
+        true
+    }
+
+    //back on solid floor, whatever was left of the hover is gone
+    pub fn land( &mut self )
+    {
+        self.hovering = 0;
+    }
+
+    //a fingerprint for the game checksum
+    pub fn get_bits( &self ) -> u32
+    {
+        self.speed_ticks ^ self.double_throw_ticks << 8 ^ self.magnet_ticks << 16
+            ^ ( self.shield as u32 ) << 29 ^ ( self.hover as u32 ) << 30 ^ self.hovering << 24
+    }
+}
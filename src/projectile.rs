@@ -1,102 +1,240 @@
-use hitbox::Hitbox;
-
-use ggez::graphics;
-use ggez::Context;
+use collision::*;
+use physics::*;
 
 use tile::*;
 
-use player::*;
 use anim::*;
 
+//how far a thrown tile flies before it comes down, in pixels
+pub const PROJECTILE_RANGE : f32 = 8.0 * TILE_SPACE as f32;
+//heavy tiles weigh this many plain ones and leave whoever they hit reeling for longer
+const HEAVY_MASS_SCALE : f32 = 3.0;
+pub const HEAVY_STUN_TICKS : u32 = 60;
+
+#[derive(Clone)]
 pub struct Projectile
 {
     owner: u32,
     pos_x: f32,
     pos_y: f32,
+    prev_pos_x: f32,
+    prev_pos_y: f32,
     vel_x: f32,
     vel_y: f32,
-    hitbox: Hitbox,
+    collider: Collider,
     sprite_id: usize,
-    sprite: graphics::Image,
+    kind: TileKind,
+    travelled: f32,
     is_dead: bool,
+    //came down on an empty cell and became floor again
+    has_landed: bool,
 }
 
 impl Projectile
 {
-    pub fn new( ctx: &mut Context, owner_id: u32, pos_x: f32, pos_y: f32, vel_x: f32, vel_y: f32, sprite_id: usize ) -> Projectile
+    pub fn new( owner_id: u32, pos_x: f32, pos_y: f32, vel_x: f32, vel_y: f32, sprite_id: usize, kind: TileKind ) -> Projectile
     {
-        Projectile
+        let mut projectile = Projectile
         { 
             owner: owner_id,
             pos_x,
             pos_y,
+            prev_pos_x: pos_x,
+            prev_pos_y: pos_y,
             vel_x,
             vel_y,
-            hitbox: Hitbox::new( pos_x, pos_y, TILE_SIZE as f32, TILE_SIZE as f32 ),
+            collider: Collider::rect( TILE_SIZE as f32, TILE_SIZE as f32 ),
             sprite_id,
-            sprite: get_image(ctx, sprite_id),
+            kind,
+            travelled: 0.0,
             is_dead: false,
+            has_landed: false,
+        };
+        projectile.place_collider();
+        projectile
+    }
+
+    fn place_collider( &mut self )
+    {
+        let half_size = TILE_SIZE as f32 / 2.0;
+        let angle = self.get_angle();
+        self.collider.set_pos( self.pos_x + half_size, self.pos_y + half_size, angle );
+    }
+
+    //tiles fly edge first along their path. a square looks the same every quarter turn,
+    //so straight throws stay unrotated and diagonal ones spin up to an eighth of a turn
+    pub fn get_angle( &self ) -> f32
+    {
+        let quarter = ::std::f32::consts::FRAC_PI_2;
+        let angle = self.vel_y.atan2( self.vel_x );
+        angle - ( angle / quarter ).round() * quarter
+    }
+
+    pub fn update( &mut self, tile_map: &mut TileMap, anims: &mut Vec<Anim> )
+    {
+        self.prev_pos_x = self.pos_x;
+        self.prev_pos_y = self.pos_y;
+        self.pos_x += self.vel_x;
+        self.pos_y += self.vel_y;
+        self.place_collider();
+
+        if !self.is_dead
+        {
+            self.travelled += ( self.vel_x * self.vel_x + self.vel_y * self.vel_y ).sqrt();
+            if self.travelled >= self.get_range()
+            {
+                self.land( tile_map );
+            }
+        }
+        if self.is_dead
+        {
+            if self.kind == TileKind::Explosive
+            {
+                let half_size = TILE_SIZE as f32 / 2.0;
+                tile_map.blast( self.pos_x + half_size, self.pos_y + half_size );
+                let prefix = "/collision/collision_animation_".to_owned();
+                anims.push( Anim::new( self.pos_x - 16.0, self.pos_y - 16.0, prefix, 4, 10 ) );
+            }
+            let prefix = "/hit_animation/hit_animation_".to_owned();
+            anims.push( Anim::new_owned( self.pos_x - 16.0, self.pos_y - 16.0, prefix, 3, 4, self.owner ) );
         }
     }
 
-    pub fn update( &mut self, _ctx: &mut Context, factor: f32, anims: &mut Vec<Anim> )
+    //ice slides further, cracked tiles fall apart halfway
+    fn get_range( &self ) -> f32
     {
-        self.pos_x += self.vel_x * factor;
-        self.pos_y += self.vel_y * factor;
-        self.hitbox.top_x = self.pos_x;
-        self.hitbox.top_y = self.pos_y;
+        match self.kind
+        {
+            TileKind::Ice => PROJECTILE_RANGE * 1.5,
+            TileKind::Cracked => PROJECTILE_RANGE * 0.5,
+            _ => PROJECTILE_RANGE,
+        }
+    }
 
-        let mut prefix = "/hit_animation/hit_animation_".to_owned();
-        if self.owner == 1
+    //fills the cell under the tile's centre if it is empty, anywhere else the tile breaks
+    fn land( &mut self, tile_map: &mut TileMap )
+    {
+        if self.kind == TileKind::Cracked
         {
-            prefix += "red_";
+            self.is_dead = true;
+            return;
         }
-        else
+        let half_size = TILE_SIZE as f32 / 2.0;
+        match tile_map.get_tile_at_mut( self.pos_x + half_size, self.pos_y + half_size )
         {
-            prefix += "green_";
+            Some( ref mut tile ) if *tile.get_state() == TileState::EMPTY =>
+            {
+                tile.change_state( TileState::FULL );
+                tile.image_id = self.sprite_id;
+                tile.kind = self.kind;
+                self.has_landed = true;
+            }
+            _ => { self.is_dead = true }
         }
+    }
 
-        if self.is_dead
+    //bounces off the edges of an arena of the given size in pixels
+    pub fn bounce_off_walls( &mut self, width: f32, height: f32 )
+    {
+        let max_x = width - TILE_SPACE as f32;
+        let max_y = height - TILE_SPACE as f32;
+        if self.pos_x < 0.0
         {
-            anims.push( Anim::new(_ctx, self.pos_x - 16.0, self.pos_y - 16.0, prefix, 3, 4 ) );
+            self.pos_x = -self.pos_x;
+            self.vel_x = self.vel_x.abs();
         }
+        else if self.pos_x > max_x
+        {
+            self.pos_x = max_x * 2.0 - self.pos_x;
+            self.vel_x = -self.vel_x.abs();
+        }
+        if self.pos_y < 0.0
+        {
+            self.pos_y = -self.pos_y;
+            self.vel_y = self.vel_y.abs();
+        }
+        else if self.pos_y > max_y
+        {
+            self.pos_y = max_y * 2.0 - self.pos_y;
+            self.vel_y = -self.vel_y.abs();
+        }
+        self.place_collider();
+    }
+
+    pub fn get_pos_x( &self ) -> f32
+    {
+        self.pos_x
+    }
+
+    pub fn get_pos_y( &self ) -> f32
+    {
+        self.pos_y
     }
 
-    pub fn draw( &mut self, ctx: &mut Context )
+    pub fn get_prev_pos_x( &self ) -> f32
     {
-        //draaw shadow
-        let shadow_draw_pos : graphics::Point2 = graphics::Point2::new
-        (
-            self.pos_x + 2.0,
-            self.pos_y + 5.0
-        );
-        let color = graphics::Color::new( 0.0, 0.0, 0.0, 0.7);
-        let shadow = graphics::Image::solid( ctx, 32, color).unwrap();
-        graphics::draw(ctx, &shadow, shadow_draw_pos, 0.0);
+        self.prev_pos_x
+    }
 
-        //draw self
-        let dest_point = graphics::Point2::new( self.pos_x, self.pos_y);
-        graphics::draw(ctx, &self.sprite, dest_point, 0.0 );
+    pub fn get_prev_pos_y( &self ) -> f32
+    {
+        self.prev_pos_y
     }
 
-    pub fn is_dead( &self ) -> bool
+    pub fn get_vel_x( &self ) -> f32
+    {
+        self.vel_x
+    }
+
+    pub fn get_vel_y( &self ) -> f32
+    {
+        self.vel_y
+    }
+
+    pub fn get_sprite_id( &self ) -> usize
+    {
+        self.sprite_id
+    }
+
+    pub fn get_kind( &self ) -> TileKind
+    {
+        self.kind
+    }
+
+    pub fn get_mass( &self ) -> f32
     {
-        //we also need to check out of bounds
-        if self.pos_x < -32.0 || self.pos_y < -32.0 || self.pos_x > ::WINDOW_WIDTH as f32 || self.pos_y > ::WINDOW_HEIGHT as f32
+        match self.kind
         {
-            return true;
+            TileKind::Heavy => get_tile_mass() * HEAVY_MASS_SCALE,
+            _ => get_tile_mass(),
         }
+    }
+
+    pub fn is_dead( &self ) -> bool
+    {
         self.is_dead
     }
 
+    //broken or landed, either way no longer in the air
+    pub fn is_gone( &self ) -> bool
+    {
+        self.is_dead || self.has_landed
+    }
+
+    //flown off an arena of the given size in pixels
+    pub fn is_out_of_bounds( &self, width: f32, height: f32 ) -> bool
+    {
+        self.pos_x < -32.0 || self.pos_y < -32.0 || self.pos_x > width || self.pos_y > height
+    }
+
     pub fn kill( &mut self ) 
     {
         self.is_dead = true;
     }
 
-    pub fn get_hitbox( &self ) -> &Hitbox
+    pub fn get_collider( &self ) -> &Collider
     {
-        &self.hitbox
+        &self.collider
     }
 
     pub fn get_owner( &self ) -> u32
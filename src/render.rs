@@ -0,0 +1,582 @@
+use ggez::graphics;
+use ggez::Context;
+use ggez::GameResult;
+
+use std::collections::HashMap;
+
+use game::*;
+use player::*;
+use tile::*;
+use tilesheet::*;
+use projectile::*;
+use anim::*;
+use editor::*;
+use powerup::*;
+use game_mode::*;
+
+//below this many ticks left a crumbling tile flashes faster
+const CRUMBLE_HURRY_TICKS : u32 = 30;
+//power-ups about to disappear blink
+const POWERUP_HURRY_TICKS : u32 = 2 * TICKS_PER_SECOND;
+const POWERUP_RADIUS : f32 = 9.0;
+const SHIELD_RADIUS : f32 = 21.0;
+//the shadow of a tile falling from the sky grows to this radius as it comes down
+const SKY_SHADOW_RADIUS : f32 = 16.0;
+//an invulnerable robot blinks on and off this many ticks at a time
+const INVULNERABLE_FLASH_TICKS : u32 = 4;
+
+fn tile_missing_color() -> graphics::Color
+{
+    graphics::Color::new(0.3, 0.3, 0.3, 1.0)
+}
+
+fn shadow_color() -> graphics::Color
+{
+    graphics::Color::new( 0.0, 0.0, 0.0, 0.7)
+}
+
+fn cursor_color() -> graphics::Color
+{
+    graphics::Color::new( 1.0, 1.0, 1.0, 0.8 )
+}
+
+fn shade_color() -> graphics::Color
+{
+    graphics::Color::new( 0.0, 0.0, 0.0, 0.6 )
+}
+
+fn crumble_color() -> graphics::Color
+{
+    graphics::Color::new( 1.0, 0.4, 0.4, 1.0 )
+}
+
+fn get_powerup_color( kind: PowerupKind ) -> graphics::Color
+{
+    match kind
+    {
+        PowerupKind::Speed => graphics::Color::new( 1.0, 0.9, 0.2, 1.0 ),
+        PowerupKind::DoubleThrow => graphics::Color::new( 1.0, 0.4, 0.8, 1.0 ),
+        PowerupKind::Shield => graphics::Color::new( 0.4, 0.8, 1.0, 1.0 ),
+        PowerupKind::Magnet => graphics::Color::new( 0.9, 0.2, 0.2, 1.0 ),
+        PowerupKind::Hover => graphics::Color::new( 0.5, 1.0, 0.5, 1.0 ),
+    }
+}
+
+fn hill_color() -> graphics::Color
+{
+    graphics::Color::new( 1.0, 0.85, 0.2, 1.0 )
+}
+
+fn mark_color() -> graphics::Color
+{
+    graphics::Color::new( 0.1, 0.1, 0.1, 0.8 )
+}
+
+fn target_color() -> graphics::Color
+{
+    graphics::Color::new( 0.9, 0.15, 0.1, 1.0 )
+}
+
+fn flag_color() -> graphics::Color
+{
+    graphics::Color::new( 0.2, 0.9, 0.3, 1.0 )
+}
+
+fn turret_color() -> graphics::Color
+{
+    graphics::Color::new( 0.55, 0.55, 0.6, 1.0 )
+}
+
+//special tiles are the normal tileset tinted, so they still match the arena around them
+fn get_kind_color( kind: TileKind ) -> Option<graphics::Color>
+{
+    match kind
+    {
+        TileKind::Normal => None,
+        TileKind::Cracked => Some( graphics::Color::new( 0.85, 0.75, 0.6, 1.0 ) ),
+        TileKind::Ice => Some( graphics::Color::new( 0.6, 0.85, 1.0, 1.0 ) ),
+        TileKind::Heavy => Some( graphics::Color::new( 0.5, 0.5, 0.55, 1.0 ) ),
+        TileKind::Explosive => Some( graphics::Color::new( 1.0, 0.55, 0.3, 1.0 ) ),
+        TileKind::Conveyor( _ ) => Some( graphics::Color::new( 0.75, 0.75, 0.75, 1.0 ) ),
+    }
+}
+
+//which of the two robot sprites a player uses, and what to tint it with
+struct Palette
+{
+    red: bool,
+    tint: graphics::Color,
+}
+
+//in teams the sprite is the team's and the second robot of a team is a lighter shade of it
+fn get_palette( id: u32, teams: bool ) -> Palette
+{
+    if teams
+    {
+        let red = id % 2 == 1;
+        let tint = match ( id > 2, red )
+        {
+            ( false, _ ) => graphics::WHITE,
+            ( true, true ) => graphics::Color::new( 1.0, 0.75, 0.75, 1.0 ),
+            ( true, false ) => graphics::Color::new( 0.75, 1.0, 0.75, 1.0 ),
+        };
+        return Palette { red, tint };
+    }
+    match id
+    {
+        1 => Palette { red: true, tint: graphics::WHITE },
+        2 => Palette { red: false, tint: graphics::WHITE },
+        3 => Palette { red: true, tint: graphics::Color::new( 0.6, 0.6, 1.0, 1.0 ) },
+        _ => Palette { red: false, tint: graphics::Color::new( 1.0, 1.0, 0.4, 1.0 ) },
+    }
+}
+
+//the colour of a side's robots, for what a game mode marks as theirs
+fn get_side_color( side: u32, teams: bool ) -> graphics::Color
+{
+    let palette = get_palette( side, teams );
+    let ( red, green ) = if palette.red { ( 1.0, 0.3 ) } else { ( 0.3, 1.0 ) };
+    graphics::Color::new( red * palette.tint.r, green * palette.tint.g, 0.3 * palette.tint.b, 1.0 )
+}
+
+pub fn get_image( ctx: &mut Context, id: usize ) -> GameResult<graphics::Image>
+{
+    let path = format!( "/tiles/sunflower_{:02}.png", id );
+    graphics::Image::new( ctx, path )
+}
+
+//every image the renderer needs, loaded once instead of by the game objects themselves
+pub struct Assets
+{
+    robo: graphics::Image,
+    robo_red: graphics::Image,
+    robo_shadow: graphics::Image,
+    tile_shadow: graphics::Image,
+    tile_missing: graphics::Image,
+    tiles: HashMap<usize, graphics::Image>,
+    anim_frames: HashMap<String, graphics::Image>,
+}
+
+impl Assets
+{
+    pub fn new( ctx: &mut Context ) -> GameResult<Assets>
+    {
+        let mut tiles = HashMap::new();
+        for id in 1..( TILE_SHEET_NUM_ACROSS * TILE_SHEET_NUM_DOWN + 1 )
+        {
+            tiles.insert( id, get_image( ctx, id )? );
+        }
+        Ok( Assets
+        {
+            robo: graphics::Image::new( ctx, "/robo.png" )?,
+            robo_red: graphics::Image::new( ctx, "/robo_red.png" )?,
+            robo_shadow: graphics::Image::new( ctx, "/robo_shadow.png" )?,
+            tile_shadow: graphics::Image::solid( ctx, TILE_SIZE as u16, shadow_color() )?,
+            tile_missing: graphics::Image::solid( ctx, TILE_SIZE as u16, tile_missing_color() )?,
+            tiles,
+            anim_frames: HashMap::new(),
+        })
+    }
+
+    fn get_anim_frame( &mut self, ctx: &mut Context, path: String ) -> GameResult<&graphics::Image>
+    {
+        if !self.anim_frames.contains_key( &path )
+        {
+            let image = graphics::Image::new( ctx, path.clone() )?;
+            self.anim_frames.insert( path.clone(), image );
+        }
+        Ok( &self.anim_frames[&path] )
+    }
+}
+
+fn lerp( prev: f32, cur: f32, alpha: f32 ) -> f32
+{
+    prev + ( cur - prev ) * alpha
+}
+
+//alpha is how far we are between the last tick and the next one, used to smooth movement
+pub fn draw_game( ctx: &mut Context, assets: &mut Assets, game: &Game, alpha: f32 ) -> GameResult<()>
+{
+    draw_tile_map( ctx, assets, &game.tile_map )?;
+    for marker in game.get_mode().get_markers()
+    {
+        draw_marker( ctx, &marker, game.get_config().teams )?;
+    }
+    for powerup in game.get_powerups()
+    {
+        draw_powerup( ctx, powerup )?;
+    }
+    for player in &game.players
+    {
+        draw_player( ctx, assets, player, game.get_config().teams, alpha )?;
+    }
+    for projectile in &game.projectiles
+    {
+        draw_projectile( ctx, assets, projectile, alpha )?;
+    }
+    for anim in &game.anims
+    {
+        draw_anim( ctx, assets, anim, game.get_config().teams )?;
+    }
+    Ok(())
+}
+
+//crumbling tiles flash red, faster the closer they are to falling
+fn get_crumble_color( ticks: u32 ) -> Option<graphics::Color>
+{
+    if ticks == 0
+    {
+        return None;
+    }
+    let period = if ticks < CRUMBLE_HURRY_TICKS { 4 } else { 12 };
+    if ( ticks / period ) % 2 == 0
+    {
+        Some( crumble_color() )
+    }
+    else
+    {
+        None
+    }
+}
+
+fn draw_tile_at_pos( ctx: &mut Context, assets: &Assets, tile: &Tile, pos: graphics::Point2 ) -> GameResult<()>
+{
+    match tile.get_state()
+    {
+        TileState::FULL =>
+        {
+            let sprite = assets.tiles.get( &tile.image_id ).unwrap_or( &assets.tile_missing );
+            let param = graphics::DrawParam {
+                dest: pos,
+                color: get_crumble_color( tile.get_crumble_ticks() ).or( get_kind_color( tile.kind ) ),
+                ..Default::default()
+            };
+            graphics::draw_ex( ctx, sprite, param )?;
+            draw_kind_marks( ctx, tile.kind, pos )?;
+        }
+        TileState::EMPTY => {}
+    }
+    Ok(())
+}
+
+//a crack across cracked tiles and an arrow on conveyors, pos is the tile's top left corner
+fn draw_kind_marks( ctx: &mut Context, kind: TileKind, pos: graphics::Point2 ) -> GameResult<()>
+{
+    let size = TILE_SIZE as f32;
+    let point = |x: f32, y: f32| graphics::Point2::new( pos.x + x * size, pos.y + y * size );
+    let color = graphics::get_color( ctx );
+    graphics::set_color( ctx, mark_color() )?;
+    match kind
+    {
+        TileKind::Cracked =>
+        {
+            graphics::line( ctx, &[ point( 0.2, 0.1 ), point( 0.45, 0.4 ), point( 0.35, 0.6 ), point( 0.7, 0.9 ) ], 2.0 )?;
+        }
+        TileKind::Conveyor( dir ) =>
+        {
+            let ( dir_x, dir_y ) = dir.get_vec();
+            //tip, then the two back corners of the arrow head
+            let arrow = [
+                point( 0.5 + dir_x * 0.3, 0.5 + dir_y * 0.3 ),
+                point( 0.5 - dir_x * 0.2 - dir_y * 0.25, 0.5 - dir_y * 0.2 + dir_x * 0.25 ),
+                point( 0.5 - dir_x * 0.2 + dir_y * 0.25, 0.5 - dir_y * 0.2 - dir_x * 0.25 ),
+            ];
+            graphics::polygon( ctx, graphics::DrawMode::Fill, &arrow )?;
+        }
+        _ => {}
+    }
+    graphics::set_color( ctx, color )
+}
+
+pub fn draw_tile_map( ctx: &mut Context, assets: &Assets, tile_map: &TileMap ) -> GameResult<()>
+{
+    for y_index in 0..tile_map.get_num_tiles_y()
+    {
+        for x_index in 0..tile_map.get_num_tiles_x()
+        {
+            let tile = &tile_map.map[y_index][x_index];
+            let dest_point = graphics::Point2::new( tile.get_pos_x() as f32, tile.get_pos_y() as f32 );
+            draw_tile_at_pos( ctx, assets, tile, dest_point )?;
+        }
+    }
+    Ok(())
+}
+
+//hills and bases are outlined in their colour, a hill is filled in while someone holds it, and
+//a tile about to fall from the sky casts a growing shadow
+fn draw_marker( ctx: &mut Context, marker: &Marker, teams: bool ) -> GameResult<()>
+{
+    let pos_x = ( marker.index_x * TILE_SPACE ) as f32;
+    let pos_y = ( marker.index_y * TILE_SPACE ) as f32;
+    let rect = graphics::Rect::new( pos_x, pos_y, TILE_SIZE as f32, TILE_SIZE as f32 );
+    let color = graphics::get_color( ctx );
+    match marker.kind
+    {
+        MarkerKind::Hill =>
+        {
+            if let Some( side ) = marker.side
+            {
+                let mut fill = get_side_color( side, teams );
+                fill.a = 0.4;
+                graphics::set_color( ctx, fill )?;
+                graphics::rectangle( ctx, graphics::DrawMode::Fill, rect )?;
+            }
+            graphics::set_color( ctx, hill_color() )?;
+            graphics::rectangle( ctx, graphics::DrawMode::Line( 3.0 ), rect )?;
+        }
+        MarkerKind::Base =>
+        {
+            graphics::set_color( ctx, get_side_color( marker.side.unwrap_or( 1 ), teams ) )?;
+            graphics::rectangle( ctx, graphics::DrawMode::Line( 3.0 ), rect )?;
+        }
+        MarkerKind::Warning( ticks_left ) =>
+        {
+            let radius = ( SKY_SHADOW_RADIUS - ticks_left as f32 / 4.0 ).max( 4.0 );
+            let center = graphics::Point2::new( pos_x + TILE_SIZE as f32 / 2.0, pos_y + TILE_SIZE as f32 / 2.0 );
+            graphics::set_color( ctx, shadow_color() )?;
+            graphics::circle( ctx, graphics::DrawMode::Fill, center, radius, 0.5 )?;
+        }
+        //a red and white bullseye standing on its tile
+        MarkerKind::Target =>
+        {
+            let center = graphics::Point2::new( pos_x + TILE_SIZE as f32 / 2.0, pos_y + TILE_SIZE as f32 / 2.0 );
+            let rings = [( ROBOT_RADIUS, target_color() ), ( ROBOT_RADIUS * 0.66, graphics::WHITE ), ( ROBOT_RADIUS * 0.33, target_color() )];
+            for &( radius, ring_color ) in rings.iter()
+            {
+                graphics::set_color( ctx, ring_color )?;
+                graphics::circle( ctx, graphics::DrawMode::Fill, center, radius, 0.5 )?;
+            }
+        }
+        MarkerKind::Flag =>
+        {
+            let mut fill = flag_color();
+            fill.a = 0.4;
+            graphics::set_color( ctx, fill )?;
+            graphics::rectangle( ctx, graphics::DrawMode::Fill, rect )?;
+            graphics::set_color( ctx, flag_color() )?;
+            graphics::rectangle( ctx, graphics::DrawMode::Line( 3.0 ), rect )?;
+        }
+        MarkerKind::Turret =>
+        {
+            let center = graphics::Point2::new( pos_x + TILE_SIZE as f32 / 2.0, pos_y + TILE_SIZE as f32 / 2.0 );
+            graphics::set_color( ctx, turret_color() )?;
+            graphics::circle( ctx, graphics::DrawMode::Fill, center, TILE_SIZE as f32 / 2.0, 0.5 )?;
+            graphics::set_color( ctx, mark_color() )?;
+            graphics::circle( ctx, graphics::DrawMode::Line( 2.0 ), center, TILE_SIZE as f32 / 2.0, 0.5 )?;
+        }
+    }
+    graphics::set_color( ctx, color )
+}
+
+//a coloured disc on its tile, blinking when it is about to go
+fn draw_powerup( ctx: &mut Context, powerup: &Powerup ) -> GameResult<()>
+{
+    let ticks_left = powerup.get_ticks_left();
+    if ticks_left < POWERUP_HURRY_TICKS && ( ticks_left / 6 ) % 2 == 0
+    {
+        return Ok(());
+    }
+    let center = graphics::Point2::new( powerup.get_pos_x(), powerup.get_pos_y() );
+    let color = graphics::get_color( ctx );
+    graphics::set_color( ctx, shadow_color() )?;
+    graphics::circle( ctx, graphics::DrawMode::Fill, graphics::Point2::new( center.x + 2.0, center.y + 3.0 ), POWERUP_RADIUS, 0.5 )?;
+    graphics::set_color( ctx, get_powerup_color( powerup.kind ) )?;
+    graphics::circle( ctx, graphics::DrawMode::Fill, center, POWERUP_RADIUS, 0.5 )?;
+    graphics::set_color( ctx, mark_color() )?;
+    graphics::circle( ctx, graphics::DrawMode::Line( 2.0 ), center, POWERUP_RADIUS, 0.5 )?;
+    graphics::set_color( ctx, color )
+}
+
+//a ring for the shield and a dot in the power-up's colour for every other boost still running
+fn draw_boosts( ctx: &mut Context, boosts: &Boosts, pos_x: f32, pos_y: f32 ) -> GameResult<()>
+{
+    let color = graphics::get_color( ctx );
+    if boosts.has_shield()
+    {
+        graphics::set_color( ctx, get_powerup_color( PowerupKind::Shield ) )?;
+        graphics::circle( ctx, graphics::DrawMode::Line( 2.0 ), graphics::Point2::new( pos_x, pos_y ), SHIELD_RADIUS, 0.5 )?;
+    }
+    let active = [
+        ( PowerupKind::Speed, boosts.get_speed_scale() > 1.0 ),
+        ( PowerupKind::DoubleThrow, boosts.has_double_throw() ),
+        ( PowerupKind::Magnet, boosts.has_magnet() ),
+        ( PowerupKind::Hover, boosts.has_hover() ),
+    ];
+    let mut dot_x = pos_x - SHIELD_RADIUS;
+    for &( kind, on ) in active.iter()
+    {
+        if on
+        {
+            graphics::set_color( ctx, get_powerup_color( kind ) )?;
+            graphics::circle( ctx, graphics::DrawMode::Fill, graphics::Point2::new( dot_x, pos_y - SHIELD_RADIUS ), 3.0, 0.5 )?;
+            dot_x += 8.0;
+        }
+    }
+    graphics::set_color( ctx, color )
+}
+
+pub fn draw_player( ctx: &mut Context, assets: &Assets, player: &Player, teams: bool, alpha: f32 ) -> GameResult<()>
+{
+    if player.is_dead()
+    {
+        return Ok(());
+    }
+    if player.is_invulnerable() && ( player.get_invulnerable_ticks() / INVULNERABLE_FLASH_TICKS ) % 2 == 1
+    {
+        return Ok(());
+    }
+
+    let pos_x = lerp( player.get_prev_pos_x(), player.get_pos_x(), alpha );
+    let pos_y = lerp( player.get_prev_pos_y(), player.get_pos_y(), alpha );
+    let scale = player.get_scale();
+    let palette = get_palette( player.get_id(), teams );
+
+    //draw player shadow, further off while hovering over a hole
+    let lift = if player.get_boosts().is_hovering() { 3.0 } else { 1.0 };
+    let top_right = graphics::Point2::new(pos_x + 5.0 * lift, pos_y + 2.0 * lift);
+    let param = graphics::DrawParam {
+        dest: top_right,
+        rotation: player.get_facing_radians(),
+        offset: graphics::Point2::new(0.5, 0.5),
+        scale: graphics::Point2::new( scale, scale ),
+        ..Default::default()
+    };
+    graphics::draw_ex(ctx, &assets.robo_shadow, param )?;
+    //draw player
+    let sprite = if palette.red { &assets.robo_red } else { &assets.robo };
+    let top_right = graphics::Point2::new(pos_x, pos_y );
+    let param = graphics::DrawParam {
+        dest: top_right,
+        rotation: player.get_facing_radians(),
+        offset: graphics::Point2::new(0.5, 0.5),
+        scale: graphics::Point2::new( scale, scale ),
+        color: Some( palette.tint ),
+        ..Default::default()
+    };
+    graphics::draw_ex(ctx, sprite, param )?;
+    draw_boosts( ctx, player.get_boosts(), pos_x, pos_y )?;
+
+    if let Some( tile ) = player.get_held_tile()
+    {
+        let tile_draw_pos : graphics::Point2 = graphics::Point2::new
+        (
+            pos_x + player.get_tile_offset_x(),
+            pos_y + player.get_tile_offset_y()
+        );
+        let shadow_draw_pos : graphics::Point2 = graphics::Point2::new
+        (
+            pos_x + player.get_tile_offset_x() + 2.0,
+            pos_y + player.get_tile_offset_y() + 5.0
+        );
+        //draw the tile shadow
+        graphics::draw(ctx, &assets.tile_shadow, shadow_draw_pos, 0.0)?;
+        //then draw the tile
+        draw_tile_at_pos( ctx, assets, tile, tile_draw_pos )?;
+    }
+    Ok(())
+}
+
+pub fn draw_projectile( ctx: &mut Context, assets: &Assets, projectile: &Projectile, alpha: f32 ) -> GameResult<()>
+{
+    let pos_x = lerp( projectile.get_prev_pos_x(), projectile.get_pos_x(), alpha );
+    let pos_y = lerp( projectile.get_prev_pos_y(), projectile.get_pos_y(), alpha );
+
+    //tiles are turned around their centre, along their flight
+    let half_size = TILE_SIZE as f32 / 2.0;
+    let rotation = projectile.get_angle();
+
+    //draw shadow
+    let param = graphics::DrawParam {
+        dest: graphics::Point2::new( pos_x + half_size + 2.0, pos_y + half_size + 5.0 ),
+        rotation,
+        offset: graphics::Point2::new(0.5, 0.5),
+        ..Default::default()
+    };
+    graphics::draw_ex(ctx, &assets.tile_shadow, param)?;
+
+    //draw self
+    let param = graphics::DrawParam {
+        dest: graphics::Point2::new( pos_x + half_size, pos_y + half_size ),
+        rotation,
+        offset: graphics::Point2::new(0.5, 0.5),
+        color: get_kind_color( projectile.get_kind() ),
+        ..Default::default()
+    };
+    match assets.tiles.get( &projectile.get_sprite_id() )
+    {
+        Some( sprite ) => { graphics::draw_ex( ctx, sprite, param )?; }
+        None => { graphics::draw_ex( ctx, &assets.tile_missing, param )?; }
+    }
+    Ok(())
+}
+
+pub fn draw_anim( ctx: &mut Context, assets: &mut Assets, anim: &Anim, teams: bool ) -> GameResult<()>
+{
+    let dest_point = graphics::Point2::new( anim.get_pos_x(), anim.get_pos_y() );
+    match anim.get_owner()
+    {
+        Some( owner ) =>
+        {
+            let palette = get_palette( owner, teams );
+            let variant = if palette.red { "red_" } else { "green_" };
+            let sprite = assets.get_anim_frame( ctx, anim.get_frame_path( variant ) )?;
+            let param = graphics::DrawParam {
+                dest: dest_point,
+                color: Some( palette.tint ),
+                ..Default::default()
+            };
+            graphics::draw_ex( ctx, sprite, param )
+        }
+        None =>
+        {
+            let sprite = assets.get_anim_frame( ctx, anim.get_frame_path( "" ) )?;
+            graphics::draw(ctx, sprite, dest_point, 0.0 )
+        }
+    }
+}
+
+//darkens everything drawn so far, for text that has to stand out
+pub fn draw_shade( ctx: &mut Context ) -> GameResult<()>
+{
+    let color = graphics::get_color( ctx );
+    graphics::set_color( ctx, shade_color() )?;
+    let screen = graphics::get_screen_coordinates( ctx );
+    graphics::rectangle( ctx, graphics::DrawMode::Fill, screen )?;
+    graphics::set_color( ctx, color )
+}
+
+//the arena as it would play, with a robot on each spawn and the cell under the mouse outlined
+pub fn draw_editor( ctx: &mut Context, assets: &Assets, editor: &Editor ) -> GameResult<()>
+{
+    let arena = editor.get_arena();
+    draw_tile_map( ctx, assets, &arena.build_tile_map() )?;
+    for ( index, spawn ) in arena.spawns.iter().enumerate()
+    {
+        let player = Player::new( index as u32 + 1, spawn.pos_x, spawn.pos_y, spawn.dir );
+        draw_player( ctx, assets, &player, false, 1.0 )?;
+    }
+
+    if let Some( ( x_index, y_index ) ) = editor.get_cursor()
+    {
+        let pos = graphics::Point2::new( ( x_index * TILE_SPACE ) as f32, ( y_index * TILE_SPACE ) as f32 );
+        let cell = editor.get_arena().get_cell( x_index, y_index );
+        let places_tile = match editor.get_tool()
+        {
+            Tool::Floor => cell.is_none(),
+            Tool::Paint => cell.is_some(),
+            Tool::Spawn | Tool::Kind => false,
+        };
+        if places_tile
+        {
+            //preview what a click would put down
+            let brush = Tile::new( x_index, y_index, editor.get_brush() );
+            draw_tile_at_pos( ctx, assets, &brush, pos )?;
+        }
+        let color = graphics::get_color( ctx );
+        graphics::set_color( ctx, cursor_color() )?;
+        let rect = graphics::Rect::new( pos.x, pos.y, TILE_SIZE as f32, TILE_SIZE as f32 );
+        graphics::rectangle( ctx, graphics::DrawMode::Line( 2.0 ), rect )?;
+        graphics::set_color( ctx, color )?;
+    }
+    Ok(())
+}
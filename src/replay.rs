@@ -0,0 +1,420 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use game::*;
+use arena::*;
+use input::*;
+use powerup::*;
+use game_mode::*;
+
+const REPLAY_MAGIC : &'static [u8; 4] = b"TLRP";
+//bumped whenever the header or the rules change, older replays would only desync
+const REPLAY_VERSION : u8 = 15;
+
+const FLAG_SHRINK : u8 = 1;
+const FLAG_KNOCKBACK : u8 = 2;
+const FLAG_RICOCHET : u8 = 4;
+const FLAG_TEAMS : u8 = 8;
+const FLAG_FRIENDLY_FIRE : u8 = 16;
+const REPLAY_DIR : &'static str = "replays";
+const MAX_RUN : usize = 0xffff;
+//replays come attached to bug reports, so a header asking for more than this is refused before
+//anything is allocated for it
+const MAX_REPLAY_TICKS : usize = 4 * 60 * 60 * TICKS_PER_SECOND as usize;
+
+const MIN_SPEED : f32 = 0.25;
+const MAX_SPEED : f32 = 8.0;
+
+//a match stored as its config plus the input of every player on every tick
+pub struct Replay
+{
+    pub config: MatchConfig,
+    num_players: usize,
+    //INPUT_SIZE bytes per player per tick
+    inputs: Vec<u8>,
+    final_checksum: u64,
+}
+
+fn invalid_data( message: &str ) -> io::Error
+{
+    io::Error::new( io::ErrorKind::InvalidData, message.to_string() )
+}
+
+fn write_u16( out: &mut dyn Write, value: u16 ) -> io::Result<()>
+{
+    out.write_all( &[ value as u8, ( value >> 8 ) as u8 ] )
+}
+
+fn write_u32( out: &mut dyn Write, value: u32 ) -> io::Result<()>
+{
+    write_u16( out, value as u16 )?;
+    write_u16( out, ( value >> 16 ) as u16 )
+}
+
+fn write_u64( out: &mut dyn Write, value: u64 ) -> io::Result<()>
+{
+    write_u32( out, value as u32 )?;
+    write_u32( out, ( value >> 32 ) as u32 )
+}
+
+fn read_u8( input: &mut dyn Read ) -> io::Result<u8>
+{
+    let mut buf = [0u8; 1];
+    input.read_exact( &mut buf )?;
+    Ok( buf[0] )
+}
+
+fn read_u16( input: &mut dyn Read ) -> io::Result<u16>
+{
+    let mut buf = [0u8; 2];
+    input.read_exact( &mut buf )?;
+    Ok( buf[0] as u16 | ( buf[1] as u16 ) << 8 )
+}
+
+fn read_u32( input: &mut dyn Read ) -> io::Result<u32>
+{
+    let low = read_u16( input )? as u32;
+    let high = read_u16( input )? as u32;
+    Ok( low | high << 16 )
+}
+
+fn read_u64( input: &mut dyn Read ) -> io::Result<u64>
+{
+    let low = read_u32( input )? as u64;
+    let high = read_u32( input )? as u64;
+    Ok( low | high << 32 )
+}
+
+//len bytes of utf-8. they are read through a limit rather than into a buffer of that size, so a
+//corrupt length can't make us allocate more than the file actually holds
+fn read_string( input: &mut dyn Read, len: usize, what: &str ) -> io::Result<String>
+{
+    let mut bytes = Vec::new();
+    input.take( len as u64 ).read_to_end( &mut bytes )?;
+    if bytes.len() != len
+    {
+        return Err( io::Error::new( io::ErrorKind::UnexpectedEof, format!( "{} is cut short", what ) ) );
+    }
+    String::from_utf8( bytes ).map_err(|_| invalid_data( &format!( "{} is not utf-8", what ) ))
+}
+
+impl Replay
+{
+    pub fn new( config: MatchConfig ) -> Replay
+    {
+        Replay
+        {
+            num_players: config.num_players,
+            config,
+            inputs: Vec::new(),
+            final_checksum: 0,
+        }
+    }
+
+    pub fn record( &mut self, inputs: &[PlayerInput] )
+    {
+        for index in 0..self.num_players
+        {
+            let bytes = inputs.get( index ).cloned().unwrap_or_default().to_bytes();
+            self.inputs.extend_from_slice( &bytes );
+        }
+    }
+
+    //remembers where the recorded match ended up so playback can detect a desync
+    pub fn finish( &mut self, game: &Game )
+    {
+        self.final_checksum = game.checksum();
+    }
+
+    fn frame_size( &self ) -> usize
+    {
+        self.num_players * INPUT_SIZE
+    }
+
+    pub fn num_ticks( &self ) -> usize
+    {
+        if self.num_players == 0
+        {
+            return 0;
+        }
+        self.inputs.len() / self.frame_size()
+    }
+
+    pub fn get_inputs( &self, tick: usize ) -> Vec<PlayerInput>
+    {
+        let start = tick * self.frame_size();
+        self.inputs[start..start + self.frame_size()].chunks( INPUT_SIZE )
+            .map(|bytes| PlayerInput::from_bytes( bytes ))
+            .collect()
+    }
+
+    pub fn get_final_checksum( &self ) -> u64
+    {
+        self.final_checksum
+    }
+
+    //header, then the per tick inputs run-length encoded since they rarely change
+    pub fn write( &self, out: &mut dyn Write ) -> io::Result<()>
+    {
+        out.write_all( REPLAY_MAGIC )?;
+        out.write_all( &[ REPLAY_VERSION ] )?;
+        write_u64( out, self.config.seed )?;
+        //the whole arena goes in, so the replay still plays after the arena file is edited
+        let name = &self.config.arena.name;
+        let arena = self.config.arena.to_text();
+        if name.len() > u16::max_value() as usize || arena.len() > u32::max_value() as usize
+        {
+            return Err( io::Error::new( io::ErrorKind::InvalidInput, "arena is too big to record" ) );
+        }
+        write_u16( out, name.len() as u16 )?;
+        out.write_all( name.as_bytes() )?;
+        write_u32( out, arena.len() as u32 )?;
+        out.write_all( arena.as_bytes() )?;
+        out.write_all( &[ self.num_players as u8 ] )?;
+        let mut flags = 0;
+        if self.config.shrink { flags |= FLAG_SHRINK; }
+        if self.config.knockback { flags |= FLAG_KNOCKBACK; }
+        if self.config.ricochet { flags |= FLAG_RICOCHET; }
+        if self.config.teams { flags |= FLAG_TEAMS; }
+        if self.config.friendly_fire { flags |= FLAG_FRIENDLY_FIRE; }
+        out.write_all( &[ flags, self.config.rounds_to_win as u8, self.config.hit_points as u8, self.config.lives as u8 ] )?;
+        out.write_all( &[ self.config.mode.get_index() as u8 ] )?;
+        write_u32( out, self.config.powerups.interval_ticks )?;
+        out.write_all( &[ self.config.powerups.max_on_floor as u8, self.config.powerups.get_pool_bits() ] )?;
+        write_u32( out, self.num_ticks() as u32 )?;
+        write_u64( out, self.final_checksum )?;
+
+        let frame_size = self.frame_size();
+        let mut tick = 0;
+        while tick < self.num_ticks()
+        {
+            let frame = &self.inputs[tick * frame_size..( tick + 1 ) * frame_size];
+            let mut run = 1;
+            while tick + run < self.num_ticks() && run < MAX_RUN
+            {
+                let start = ( tick + run ) * frame_size;
+                if &self.inputs[start..start + frame_size] != frame
+                {
+                    break;
+                }
+                run += 1;
+            }
+            write_u16( out, run as u16 )?;
+            out.write_all( frame )?;
+            tick += run;
+        }
+        Ok(())
+    }
+
+    pub fn read( input: &mut dyn Read ) -> io::Result<Replay>
+    {
+        let mut magic = [0u8; 4];
+        input.read_exact( &mut magic )?;
+        if &magic != REPLAY_MAGIC
+        {
+            return Err( invalid_data( "not a Tile League replay" ) );
+        }
+        if read_u8( input )? != REPLAY_VERSION
+        {
+            return Err( invalid_data( "unsupported replay version" ) );
+        }
+        let seed = read_u64( input )?;
+        let name_len = read_u16( input )? as usize;
+        let name = read_string( input, name_len, "arena name" )?;
+        let text_len = read_u32( input )? as usize;
+        let text = read_string( input, text_len, "arena" )?;
+        let arena = Arena::parse( &name, &text ).map_err(|e| invalid_data( &e ))?;
+        let num_players = read_u8( input )? as usize;
+        if num_players == 0 || num_players > MAX_PLAYERS || num_players > arena.spawns.len()
+        {
+            return Err( invalid_data( "bad player count" ) );
+        }
+        let flags = read_u8( input )?;
+        let rounds_to_win = read_u8( input )? as u32;
+        let hit_points = read_u8( input )? as u32;
+        let lives = read_u8( input )? as u32;
+        //none of them can be 0, the match would be over before it starts
+        if rounds_to_win == 0 || hit_points == 0 || lives == 0
+        {
+            return Err( invalid_data( "rounds to win, hit points and lives have to be at least 1" ) );
+        }
+        let mode = GameModeKind::from_index( read_u8( input )? as usize ).ok_or_else(|| invalid_data( "unknown game mode" ))?;
+        let mut powerups = PowerupConfig::default();
+        powerups.interval_ticks = read_u32( input )?;
+        powerups.max_on_floor = read_u8( input )? as usize;
+        powerups.set_pool_bits( read_u8( input )? );
+        let num_ticks = read_u32( input )? as usize;
+        let final_checksum = read_u64( input )?;
+
+        let frame_size = num_players * INPUT_SIZE;
+        let input_size = match num_ticks.checked_mul( frame_size )
+        {
+            Some( size ) if num_ticks <= MAX_REPLAY_TICKS => size,
+            _ => return Err( invalid_data( "replay is too long" ) ),
+        };
+        let mut inputs = Vec::with_capacity( input_size );
+        let mut frame = vec![0u8; frame_size];
+        while inputs.len() < input_size
+        {
+            let run = read_u16( input )? as usize;
+            input.read_exact( &mut frame )?;
+            for _ in 0..run
+            {
+                inputs.extend_from_slice( &frame );
+            }
+        }
+        if inputs.len() != input_size
+        {
+            return Err( invalid_data( "replay input runs overflow the tick count" ) );
+        }
+
+        Ok( Replay
+        {
+            config: MatchConfig
+            {
+                seed,
+                arena,
+                num_players,
+                shrink: flags & FLAG_SHRINK != 0,
+                rounds_to_win,
+                knockback: flags & FLAG_KNOCKBACK != 0,
+                ricochet: flags & FLAG_RICOCHET != 0,
+                powerups,
+                hit_points,
+                lives,
+                teams: flags & FLAG_TEAMS != 0,
+                friendly_fire: flags & FLAG_FRIENDLY_FIRE != 0,
+                mode,
+            },
+            num_players,
+            inputs,
+            final_checksum,
+        })
+    }
+
+    pub fn load( path: &Path ) -> io::Result<Replay>
+    {
+        let mut reader = BufReader::new( File::open( path )? );
+        Replay::read( &mut reader )
+    }
+
+    pub fn save( &self, path: &Path ) -> io::Result<()>
+    {
+        let mut writer = BufWriter::new( File::create( path )? );
+        self.write( &mut writer )?;
+        writer.flush()
+    }
+
+    //saves under replays/ named after the seed, which is unique enough per match
+    pub fn save_to_replay_dir( &self ) -> io::Result<PathBuf>
+    {
+        fs::create_dir_all( REPLAY_DIR )?;
+        let path = Path::new( REPLAY_DIR ).join( format!( "match_{:016x}.tlr", self.config.seed ) );
+        self.save( &path )?;
+        Ok( path )
+    }
+}
+
+//drives a Game from a Replay, with pause, single stepping and variable speed
+pub struct Playback
+{
+    replay: Replay,
+    next_tick: usize,
+    paused: bool,
+    speed: f32,
+    budget: f32,
+}
+
+impl Playback
+{
+    pub fn new( replay: Replay ) -> Playback
+    {
+        Playback
+        {
+            replay,
+            next_tick: 0,
+            paused: false,
+            speed: 1.0,
+            budget: 0.0,
+        }
+    }
+
+    pub fn new_game( &mut self ) -> Game
+    {
+        self.next_tick = 0;
+        self.budget = 0.0;
+        Game::new( self.replay.config.clone() )
+    }
+
+    //called once per real tick, runs as many replay ticks as the speed allows
+    pub fn update( &mut self, game: &mut Game )
+    {
+        if self.paused
+        {
+            return;
+        }
+        self.budget += self.speed;
+        while self.budget >= 1.0
+        {
+            self.budget -= 1.0;
+            self.step( game );
+        }
+    }
+
+    pub fn step( &mut self, game: &mut Game )
+    {
+        if self.is_finished()
+        {
+            return;
+        }
+        let inputs = self.replay.get_inputs( self.next_tick );
+        game.tick( &inputs );
+        self.next_tick += 1;
+    }
+
+    pub fn toggle_pause( &mut self )
+    {
+        self.paused = !self.paused;
+    }
+
+    pub fn is_paused( &self ) -> bool
+    {
+        self.paused
+    }
+
+    pub fn faster( &mut self )
+    {
+        self.speed = ( self.speed * 2.0 ).min( MAX_SPEED );
+    }
+
+    pub fn slower( &mut self )
+    {
+        self.speed = ( self.speed / 2.0 ).max( MIN_SPEED );
+    }
+
+    pub fn get_speed( &self ) -> f32
+    {
+        self.speed
+    }
+
+    pub fn get_next_tick( &self ) -> usize
+    {
+        self.next_tick
+    }
+
+    pub fn get_replay( &self ) -> &Replay
+    {
+        &self.replay
+    }
+
+    pub fn is_finished( &self ) -> bool
+    {
+        self.next_tick >= self.replay.num_ticks()
+    }
+
+    pub fn is_desynced( &self, game: &Game ) -> bool
+    {
+        self.is_finished() && game.checksum() != self.replay.get_final_checksum()
+    }
+}
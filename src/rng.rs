@@ -0,0 +1,76 @@
+use game::Fnv;
+
+//splitmix64, small and spelled out so a seed gives the same numbers on every machine
+#[derive(Clone)]
+pub struct Rng
+{
+    state: u64,
+}
+
+impl Rng
+{
+    pub fn new( seed: u64 ) -> Rng
+    {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64( &mut self ) -> u64
+    {
+        self.state = self.state.wrapping_add( 0x9e3779b97f4a7c15 );
+        let mut value = self.state;
+        value = ( value ^ ( value >> 30 ) ).wrapping_mul( 0xbf58476d1ce4e5b9 );
+        value = ( value ^ ( value >> 27 ) ).wrapping_mul( 0x94d049bb133111eb );
+        value ^ ( value >> 31 )
+    }
+
+    //a number from 0 up to but not including bound, which must not be 0
+    pub fn next_below( &mut self, bound: usize ) -> usize
+    {
+        ( self.next_u64() % bound as u64 ) as usize
+    }
+}
+
+//who a stream of random numbers belongs to. every subsystem draws from its own, so drawing more
+//or less often in one of them never changes what the others get
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RngStream
+{
+    Powerups,
+    GameMode,
+}
+
+pub const NUM_RNG_STREAMS : usize = 2;
+
+//all the randomness of a match. it is seeded by the match and owned by the game state, so every
+//snapshot carries it along and a replay or a rolled back game draws exactly the same numbers again
+#[derive(Clone)]
+pub struct GameRng
+{
+    streams: Vec<Rng>,
+}
+
+impl GameRng
+{
+    pub fn new( seed: u64 ) -> GameRng
+    {
+        //the streams start from successive numbers of the seed's own stream, far apart from each other
+        let mut seeder = Rng::new( seed );
+        GameRng
+        {
+            streams: ( 0..NUM_RNG_STREAMS ).map(|_| Rng::new( seeder.next_u64() )).collect(),
+        }
+    }
+
+    pub fn get( &mut self, stream: RngStream ) -> &mut Rng
+    {
+        &mut self.streams[stream as usize]
+    }
+
+    pub fn hash( &self, hash: &mut Fnv )
+    {
+        for stream in &self.streams
+        {
+            hash.write_u64( stream.state );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use game::*;
+use input::*;
+use replay::*;
+
+//how many ticks we may run ahead of the last input we actually received from the peer
+pub const MAX_PREDICTION : usize = 20;
+const CHECKSUM_INTERVAL : usize = 60;
+const CHECKSUM_HISTORY : usize = 16;
+
+//keeps a confirmed snapshot of the game where every input is known, and a predicted game
+//on top of it. when late remote inputs disagree with what we guessed, the predicted game is
+//thrown away and resimulated from the snapshot.
+pub struct Rollback
+{
+    local_index: usize,
+    confirmed: Game,
+    current: Game,
+    local_inputs: Vec<PlayerInput>,
+    remote_inputs: Vec<PlayerInput>,
+    predictions: Vec<PlayerInput>,
+    checksums: VecDeque<( usize, u64 )>,
+    recording: Replay,
+    desynced: bool,
+}
+
+fn predict( last: Option<&PlayerInput> ) -> PlayerInput
+{
+    let mut input = last.cloned().unwrap_or_default();
+    input.action = false;
+    input
+}
+
+impl Rollback
+{
+    pub fn new( config: MatchConfig, local_index: usize ) -> Rollback
+    {
+        let game = Game::new( config.clone() );
+        Rollback
+        {
+            local_index,
+            confirmed: game.clone(),
+            current: game,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            predictions: Vec::new(),
+            checksums: VecDeque::new(),
+            recording: Replay::new( config ),
+            desynced: false,
+        }
+    }
+
+    fn frame( &self, local: PlayerInput, remote: PlayerInput ) -> Vec<PlayerInput>
+    {
+        let mut frame = vec![remote; self.confirmed.players.len()];
+        frame[self.local_index] = local;
+        frame
+    }
+
+    pub fn can_advance( &self ) -> bool
+    {
+        self.local_inputs.len() < self.remote_inputs.len() + MAX_PREDICTION
+    }
+
+    pub fn add_local_input( &mut self, input: PlayerInput )
+    {
+        let tick = self.local_inputs.len();
+        self.local_inputs.push( input );
+        let remote = match self.remote_inputs.get( tick )
+        {
+            Some( remote ) => *remote,
+            None => predict( self.remote_inputs.last() ),
+        };
+        self.predictions.push( remote );
+        let frame = self.frame( input, remote );
+        self.current.tick( &frame );
+        self.advance_confirmed();
+    }
+
+    //inputs the peer sent for ticks start..start + inputs.len(), usually overlapping what we have
+    pub fn add_remote_inputs( &mut self, start: usize, inputs: &[PlayerInput] )
+    {
+        if start > self.remote_inputs.len()
+        {
+            return;
+        }
+        let first_new = self.remote_inputs.len();
+        let skip = first_new - start;
+        if skip >= inputs.len()
+        {
+            return;
+        }
+        self.remote_inputs.extend_from_slice( &inputs[skip..] );
+
+        let mispredicted = ( first_new..self.remote_inputs.len().min( self.predictions.len() ) )
+            .any(|tick| self.predictions[tick] != self.remote_inputs[tick]);
+        self.advance_confirmed();
+        if mispredicted
+        {
+            self.resimulate();
+        }
+    }
+
+    fn advance_confirmed( &mut self )
+    {
+        let known = self.local_inputs.len().min( self.remote_inputs.len() );
+        let mut tick = self.recording.num_ticks();
+        while tick < known
+        {
+            let frame = self.frame( self.local_inputs[tick], self.remote_inputs[tick] );
+            self.confirmed.tick( &frame );
+            self.recording.record( &frame );
+            tick += 1;
+            if tick % CHECKSUM_INTERVAL == 0
+            {
+                self.checksums.push_back( ( tick, self.confirmed.checksum() ) );
+                if self.checksums.len() > CHECKSUM_HISTORY
+                {
+                    self.checksums.pop_front();
+                }
+            }
+        }
+    }
+
+    fn resimulate( &mut self )
+    {
+        self.current = self.confirmed.clone();
+        for tick in self.recording.num_ticks()..self.local_inputs.len()
+        {
+            let remote = match self.remote_inputs.get( tick )
+            {
+                Some( remote ) => *remote,
+                None => predict( self.remote_inputs.last() ),
+            };
+            self.predictions[tick] = remote;
+            let frame = self.frame( self.local_inputs[tick], remote );
+            self.current.tick( &frame );
+        }
+    }
+
+    //the peer's checksum for a confirmed tick, a mismatch means the simulations diverged
+    pub fn check_remote_checksum( &mut self, tick: usize, checksum: u64 )
+    {
+        for &( our_tick, ours ) in &self.checksums
+        {
+            if our_tick == tick && ours != checksum
+            {
+                self.desynced = true;
+            }
+        }
+    }
+
+    pub fn get_latest_checksum( &self ) -> Option<( usize, u64 )>
+    {
+        self.checksums.back().cloned()
+    }
+
+    pub fn get_local_inputs( &self ) -> &[PlayerInput]
+    {
+        &self.local_inputs
+    }
+
+    pub fn num_remote_inputs( &self ) -> usize
+    {
+        self.remote_inputs.len()
+    }
+
+    pub fn get_current( &self ) -> &Game
+    {
+        &self.current
+    }
+
+    pub fn get_confirmed( &self ) -> &Game
+    {
+        &self.confirmed
+    }
+
+    pub fn get_recording_mut( &mut self ) -> &mut Replay
+    {
+        &mut self.recording
+    }
+
+    pub fn is_desynced( &self ) -> bool
+    {
+        self.desynced
+    }
+}
@@ -0,0 +1,118 @@
+use tile::*;
+
+//when and how fast the floor of an arena crumbles away in a shrinking match, in ticks
+#[derive(Clone, PartialEq, Debug)]
+pub struct ShrinkSchedule
+{
+    //grace period before the first tile starts to crack
+    pub start_tick: u64,
+    //a new tile starts to crack this often
+    pub interval_ticks: u64,
+    //how long a tile flashes before it falls away
+    pub warning_ticks: u32,
+}
+
+impl Default for ShrinkSchedule
+{
+    fn default() -> ShrinkSchedule
+    {
+        ShrinkSchedule
+        {
+            start_tick: 600,
+            interval_ticks: 15,
+            warning_ticks: 90,
+        }
+    }
+}
+
+//the cells of the arena in the order they fall away, from the outside in. next is how far in the
+//floor has gone, anything behind it should be a hole
+#[derive(Clone)]
+pub struct Shrink
+{
+    schedule: ShrinkSchedule,
+    order: Vec<( usize, usize )>,
+    next: usize,
+}
+
+//every cell of the map, clockwise around the outermost ring first and then spiralling inward
+fn get_spiral( num_tiles_x: usize, num_tiles_y: usize ) -> Vec<( usize, usize )>
+{
+    let mut cells = Vec::with_capacity( num_tiles_x * num_tiles_y );
+    if num_tiles_x == 0 || num_tiles_y == 0
+    {
+        return cells;
+    }
+    let ( mut left, mut top ) = ( 0, 0 );
+    let ( mut right, mut bottom ) = ( num_tiles_x - 1, num_tiles_y - 1 );
+    while left <= right && top <= bottom
+    {
+        for x_index in left..right + 1 { cells.push( ( x_index, top ) ); }
+        for y_index in top + 1..bottom + 1 { cells.push( ( right, y_index ) ); }
+        if top < bottom
+        {
+            for x_index in ( left..right ).rev() { cells.push( ( x_index, bottom ) ); }
+        }
+        if left < right
+        {
+            for y_index in ( top + 1..bottom ).rev() { cells.push( ( left, y_index ) ); }
+        }
+        if right == 0 || bottom == 0
+        {
+            break;
+        }
+        left += 1;
+        top += 1;
+        right -= 1;
+        bottom -= 1;
+    }
+    cells
+}
+
+impl Shrink
+{
+    pub fn new( schedule: ShrinkSchedule, tile_map: &TileMap ) -> Shrink
+    {
+        //every cell and not just the floor there is now, tiles thrown into holes have to go too
+        let order = get_spiral( tile_map.get_num_tiles_x(), tile_map.get_num_tiles_y() );
+        Shrink
+        {
+            schedule,
+            order,
+            next: 0,
+        }
+    }
+
+    //cracks a tile when the schedule says so. a tile thrown into the part that has already fallen
+    //away goes first, then the next one further in, holes are skipped
+    pub fn update( &mut self, tick: u64, tile_map: &mut TileMap )
+    {
+        if tick < self.schedule.start_tick || ( tick - self.schedule.start_tick ) % self.schedule.interval_ticks.max( 1 ) != 0
+        {
+            return;
+        }
+        let refilled = self.order[..self.next].iter()
+            .cloned()
+            .find(|&( x_index, y_index )| Shrink::can_crumble( &tile_map.map[y_index][x_index] ));
+        if let Some( ( x_index, y_index ) ) = refilled
+        {
+            tile_map.map[y_index][x_index].start_crumbling( self.schedule.warning_ticks );
+            return;
+        }
+        while let Some( &( x_index, y_index ) ) = self.order.get( self.next )
+        {
+            self.next += 1;
+            let tile = &mut tile_map.map[y_index][x_index];
+            if Shrink::can_crumble( tile )
+            {
+                tile.start_crumbling( self.schedule.warning_ticks );
+                return;
+            }
+        }
+    }
+
+    fn can_crumble( tile: &Tile ) -> bool
+    {
+        *tile.get_state() == TileState::FULL && !tile.is_crumbling()
+    }
+}
@@ -1,148 +1,316 @@
-use ggez::graphics;
-use ggez::Context;
-use ggez::GameResult;
-
-use tilesheet::SheetMap;
-use projectile::Projectile;
-
 pub const TILE_SEPARATOR : usize = 2;
 pub const TILE_SIZE : usize = 32;
 pub const TILE_SPACE : usize = TILE_SEPARATOR + TILE_SIZE;
+//how long a cracked tile holds once a robot steps on it
+const CRACKED_CRUMBLE_TICKS : u32 = 40;
+
+use player::Direction;
 
 type TileRow = Vec<Tile>;
 
+#[derive(Clone)]
 pub struct TileMap
 {
     pub map: Vec<TileRow>,
     num_tiles_x: usize,
     num_tiles_y: usize,
-    sheetmap: SheetMap, 
 }
 
 impl TileMap
 {
-    pub fn new( _ctx: &mut Context, num_tiles_x: usize, num_tiles_y: usize ) -> TileMap
+    //rows of tiles, all the same length
+    pub fn new( map: Vec<TileRow> ) -> TileMap
+    {
+        TileMap
+        {
+            num_tiles_x: map.first().map_or( 0, |row| row.len() ),
+            num_tiles_y: map.len(),
+            map,
+        }
+    }
+
+    //the tile under a point in pixels, None when the point is off the map
+    pub fn get_tile_at( &self, pos_x: f32, pos_y: f32 ) -> Option<&Tile>
     {
-        let mut res = TileMap
+        if pos_x < 0.0 || pos_y < 0.0
         {
-            map: Vec::new(),
-            num_tiles_x,
-            num_tiles_y,
-            sheetmap: SheetMap::new( _ctx )
-        };
+            return None;
+        }
+        let index_x = pos_x as usize / TILE_SPACE;
+        let index_y = pos_y as usize / TILE_SPACE;
+        self.map.get( index_y ).and_then(|row| row.get( index_x ))
+    }
+
+    pub fn get_tile_at_mut( &mut self, pos_x: f32, pos_y: f32 ) -> Option<&mut Tile>
+    {
+        if pos_x < 0.0 || pos_y < 0.0
+        {
+            return None;
+        }
+        let index_x = pos_x as usize / TILE_SPACE;
+        let index_y = pos_y as usize / TILE_SPACE;
+        self.map.get_mut( index_y ).and_then(|row| row.get_mut( index_x ))
+    }
 
-        for y_index in 0..num_tiles_y
+    //the kind of the floor under a point, None where there is no floor
+    pub fn get_floor_kind_at( &self, pos_x: f32, pos_y: f32 ) -> Option<TileKind>
+    {
+        match self.get_tile_at( pos_x, pos_y )
+        {
+            Some( tile ) if *tile.get_state() == TileState::FULL => Some( tile.kind ),
+            _ => None,
+        }
+    }
+
+    pub fn can_pick_up_at( &self, pos_x: f32, pos_y: f32 ) -> bool
+    {
+        self.get_floor_kind_at( pos_x, pos_y ).map_or( false, |kind| kind.can_pick_up() )
+    }
+
+    //a robot standing here sets a cracked tile crumbling
+    pub fn step_on( &mut self, pos_x: f32, pos_y: f32 )
+    {
+        if let Some( tile ) = self.get_tile_at_mut( pos_x, pos_y )
+        {
+            if tile.state == TileState::FULL && tile.kind == TileKind::Cracked && !tile.is_crumbling()
+            {
+                tile.start_crumbling( CRACKED_CRUMBLE_TICKS );
+            }
+        }
+    }
+
+    //empties the cell under a point and the eight around it
+    pub fn blast( &mut self, pos_x: f32, pos_y: f32 )
+    {
+        if pos_x < 0.0 || pos_y < 0.0
         {
-            let mut tile_row = Vec::new();
-            for x_index in 0..num_tiles_x
+            return;
+        }
+        let index_x = pos_x as usize / TILE_SPACE;
+        let index_y = pos_y as usize / TILE_SPACE;
+        for y_index in index_y.saturating_sub( 1 )..index_y + 2
+        {
+            for x_index in index_x.saturating_sub( 1 )..index_x + 2
             {
-                //edge tiles
-                if y_index < 3 || y_index >= num_tiles_y - 3 || x_index < 3 || x_index >= num_tiles_x - 3 
+                if let Some( tile ) = self.map.get_mut( y_index ).and_then(|row| row.get_mut( x_index ))
                 {
-                    tile_row.push( Tile::new_empty( _ctx, x_index, y_index ) );
+                    tile.change_state( TileState::EMPTY );
                 }
-                else
+            }
+        }
+    }
+
+    pub fn is_floor_at( &self, pos_x: f32, pos_y: f32 ) -> bool
+    {
+        match self.get_tile_at( pos_x, pos_y )
+        {
+            Some( tile ) => *tile.get_state() == TileState::FULL,
+            None => false,
+        }
+    }
+
+    //floor that is not about to fall away
+    pub fn is_safe_at( &self, pos_x: f32, pos_y: f32 ) -> bool
+    {
+        match self.get_tile_at( pos_x, pos_y )
+        {
+            Some( tile ) => *tile.get_state() == TileState::FULL && !tile.is_crumbling(),
+            None => false,
+        }
+    }
+
+    //index x and y of every floor tile that is not about to fall away
+    pub fn get_safe_cells( &self ) -> Vec<( usize, usize )>
+    {
+        let mut cells = Vec::new();
+        for ( index_y, row ) in self.map.iter().enumerate()
+        {
+            for ( index_x, tile ) in row.iter().enumerate()
+            {
+                if *tile.get_state() == TileState::FULL && !tile.is_crumbling()
                 {
-                    //plus one on indices because we have a border
-                    tile_row.push( Tile::new( _ctx, x_index, y_index, res.sheetmap.map[y_index-3][x_index-3] ) );
+                    cells.push( ( index_x, index_y ) );
                 }
             }
+        }
+        cells
+    }
 
-            res.map.push( tile_row );
+    //centre of the safe floor tile closest to a point, None once the whole floor is gone
+    pub fn find_safe_floor_near( &self, pos_x: f32, pos_y: f32 ) -> Option<( f32, f32 )>
+    {
+        let mut closest : Option<( f32, f32, f32 )> = None;
+        for row in &self.map
+        {
+            for tile in row
+            {
+                if *tile.get_state() != TileState::FULL || tile.is_crumbling()
+                {
+                    continue;
+                }
+                let center_x = ( tile.get_pos_x() + TILE_SIZE / 2 ) as f32;
+                let center_y = ( tile.get_pos_y() + TILE_SIZE / 2 ) as f32;
+                let dist = ( center_x - pos_x ) * ( center_x - pos_x ) + ( center_y - pos_y ) * ( center_y - pos_y );
+                if closest.map_or( true, |( _, _, best )| dist < best )
+                {
+                    closest = Some( ( center_x, center_y, dist ) );
+                }
+            }
         }
-        res
+        closest.map(|( center_x, center_y, _ )| ( center_x, center_y ))
     }
-    
-    pub fn draw( &mut self, ctx: &mut Context ) -> GameResult<()>
+
+    //counts down crumbling tiles and drops the ones whose time is up
+    pub fn update( &mut self )
     {
-        for y_index in 0..self.num_tiles_y
+        for row in &mut self.map
         {
-            for x_index in 0..self.num_tiles_x
+            for tile in row
             {
-                self.map[y_index][x_index].draw( ctx );
+                tile.update();
             }
         }
-        Ok(())
+    }
+
+    pub fn get_num_tiles_x( &self ) -> usize
+    {
+        self.num_tiles_x
+    }
+
+    pub fn get_num_tiles_y( &self ) -> usize
+    {
+        self.num_tiles_y
+    }
+}
+
+//what a tile does besides being floor, it keeps its kind when picked up and thrown
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TileKind
+{
+    Normal,
+    //starts crumbling as soon as a robot walks on it
+    Cracked,
+    //robots barely grip on it
+    Ice,
+    //flies slower but stuns whoever it hits
+    Heavy,
+    //blows away the floor around wherever it breaks
+    Explosive,
+    //carries robots standing on it along, and is bolted down
+    Conveyor( Direction ),
+}
+
+//every kind, in the order the editor cycles through them
+pub const TILE_KINDS : [TileKind; 9] = [
+    TileKind::Normal,
+    TileKind::Cracked,
+    TileKind::Ice,
+    TileKind::Heavy,
+    TileKind::Explosive,
+    TileKind::Conveyor( Direction::UP ),
+    TileKind::Conveyor( Direction::RIGHT ),
+    TileKind::Conveyor( Direction::DOWN ),
+    TileKind::Conveyor( Direction::LEFT ),
+];
+
+impl TileKind
+{
+    //what follows the image id in an arena file
+    pub fn get_suffix( &self ) -> &'static str
+    {
+        match *self
+        {
+            TileKind::Normal => "",
+            TileKind::Cracked => "c",
+            TileKind::Ice => "i",
+            TileKind::Heavy => "h",
+            TileKind::Explosive => "x",
+            TileKind::Conveyor( Direction::UP ) => "^",
+            TileKind::Conveyor( Direction::RIGHT ) => ">",
+            TileKind::Conveyor( Direction::DOWN ) => "v",
+            TileKind::Conveyor( Direction::LEFT ) => "<",
+        }
+    }
+
+    pub fn from_suffix( suffix: &str ) -> Option<TileKind>
+    {
+        TILE_KINDS.iter().cloned().find(|kind| kind.get_suffix() == suffix)
+    }
+
+    //position in TILE_KINDS, stable enough to hash
+    pub fn get_index( &self ) -> usize
+    {
+        TILE_KINDS.iter().position(|kind| kind == self).unwrap_or( 0 )
+    }
+
+    pub fn next( &self ) -> TileKind
+    {
+        TILE_KINDS[( self.get_index() + 1 ) % TILE_KINDS.len()]
+    }
+
+    pub fn can_pick_up( &self ) -> bool
+    {
+        match *self
+        {
+            TileKind::Conveyor( _ ) => false,
+            _ => true,
+        }
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum TileState 
 {
     FULL,
     EMPTY,
 }
 
+#[derive(Clone)]
 pub struct Tile
 {
     pos_x: usize,
     pos_y: usize,
     state: TileState,
-    sprite: graphics::Image,
     pub image_id: usize,
-}
-
-fn tile_test_color() -> graphics::Color
-{
-    graphics::Color::new(0.7, 0.8, 0.9, 1.0)
-}
-
-fn tile_missing_color() -> graphics::Color
-{
-    graphics::Color::new(0.3, 0.3, 0.3, 1.0)
-}
-
-pub fn get_image( ctx: &mut Context, id: usize ) -> graphics::Image
-{
-    let path = format!( "/tiles/sunflower_{:02}.png", id );
-    graphics::Image::new( ctx, path ).unwrap()
+    pub kind: TileKind,
+    crumble: u32,
 }
 
 impl Tile
 {
-    pub fn new_empty( ctx: &mut Context, index_x: usize, index_y: usize ) -> Tile
+    pub fn new_empty( index_x: usize, index_y: usize ) -> Tile
     { 
         Tile
         {
             pos_x: index_x * ( TILE_SIZE + TILE_SEPARATOR ),
             pos_y: index_y * ( TILE_SIZE + TILE_SEPARATOR ),
             state: TileState::EMPTY,
-            sprite: graphics::Image::solid( ctx, 32, tile_missing_color() ).unwrap(),
             image_id: 0,
+            kind: TileKind::Normal,
+            crumble: 0,
         }
     }
-    pub fn new( ctx: &mut Context, index_x: usize, index_y: usize, image_id: usize ) -> Tile
+    pub fn new( index_x: usize, index_y: usize, image_id: usize ) -> Tile
     { 
         Tile
         {
             pos_x: index_x * ( TILE_SIZE + TILE_SEPARATOR ),
             pos_y: index_y * ( TILE_SIZE + TILE_SEPARATOR ),
             state: TileState::FULL,
-            sprite: get_image( ctx, image_id),
-            image_id: image_id
+            image_id: image_id,
+            kind: TileKind::Normal,
+            crumble: 0,
         }
     }
 
-    pub fn draw_at_pos( &mut self, ctx: &mut Context, pos: &graphics::Point2 )
+    pub fn get_pos_x( &self ) -> usize
     {
-        match self.state
-        {
-            TileState::FULL =>
-            {
-                //self.sprite = graphics::Image::solid( ctx, 32, tile_test_color() ).unwrap();
-                graphics::draw(ctx, &self.sprite, *pos, 0.0 );
-            }
-            TileState::EMPTY =>
-            {
-                //self.sprite = graphics::Image::solid( ctx, 32, tile_missing_color() ).unwrap();
-            }
-        }
-    } 
+        self.pos_x
+    }
 
-    pub fn draw( &mut self, ctx: &mut Context )
+    pub fn get_pos_y( &self ) -> usize
     {
-        let dest_point = graphics::Point2::new( self.pos_x as f32, self.pos_y as f32 );
-        self.draw_at_pos(ctx, &dest_point);
+        self.pos_y
     }
 
     pub fn get_state( &self ) -> &TileState
@@ -153,5 +321,36 @@ impl Tile
     pub fn change_state( &mut self, tile_state: TileState ) 
     {
         self.state = tile_state;
+        self.crumble = 0;
+    }
+
+    //the tile stays walkable for the given number of ticks, then falls away
+    pub fn start_crumbling( &mut self, ticks: u32 )
+    {
+        self.crumble = ticks.max( 1 );
+    }
+
+    pub fn is_crumbling( &self ) -> bool
+    {
+        self.crumble > 0
+    }
+
+    //ticks left before a crumbling tile falls, 0 when it is not crumbling
+    pub fn get_crumble_ticks( &self ) -> u32
+    {
+        self.crumble
+    }
+
+    fn update( &mut self )
+    {
+        if self.crumble == 0
+        {
+            return;
+        }
+        self.crumble -= 1;
+        if self.crumble == 0
+        {
+            self.state = TileState::EMPTY;
+        }
     }
 }
@@ -1,12 +1,9 @@
-use ggez::graphics;
-use ggez::Context;
-use ggez::GameResult;
-
 pub const TILE_SHEET_NUM_ACROSS : usize = 8;
 pub const TILE_SHEET_NUM_DOWN : usize = 16; 
 
 type SheetRow = Vec<usize>;
 
+#[derive(Clone)]
 pub struct SheetMap
 {
     pub map: Vec<SheetRow>,
@@ -17,7 +14,7 @@ pub struct SheetMap
 
 impl SheetMap
 {
-    pub fn new( _ctx: &mut Context ) -> SheetMap
+    pub fn new() -> SheetMap
     {
         let mut res = SheetMap
         {